@@ -1,30 +1,94 @@
+use crate::arena::Arena;
 use crate::heap::LAlloc;
-use crate::object::OBJECT_ALIGNMENT;
+use crate::object::{BoxKind, PackedPtr, RawCons, TagType, UnpackedPtr, OBJECT_ALIGNMENT};
 use crate::root::RootList;
 use crate::root::RootNode;
+use crate::safepoint::Safepoint;
 use crate::sorted_vec::SortedVec;
+use crate::source::{SourceMap, SourcePos};
 use std::alloc;
-use std::collections::HashSet;
-use std::mem::size_of;
-use std::mem::take;
+use std::collections::{HashSet, VecDeque};
+use std::marker::PhantomData;
+use std::mem::{size_of, take};
 use std::pin::Pin;
-use std::ptr::NonNull;
+use std::ptr::{addr_of_mut, NonNull};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::sync::Condvar;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
 use std::sync::TryLockError;
+use std::time::{Duration, Instant};
 
-const IMMIX_LINES: usize = 254;
+const IMMIX_BLOCK_SIZE: usize = 32 * 1024;
+const IMMIX_LINES: usize = 250;
 const IMMIX_LINE_SIZE: usize = 128;
 const IMMIX_USABLE_SIZE: usize = IMMIX_LINES * IMMIX_LINE_SIZE;
-const IMMIX_META_SIZE: usize = 256;
-const IMMIX_BLOCK_SIZE: usize = IMMIX_USABLE_SIZE + IMMIX_META_SIZE;
+const IMMIX_META_SIZE: usize = IMMIX_BLOCK_SIZE - IMMIX_USABLE_SIZE;
 const IMMIX_MIN_STARTING_SIZE: usize = 8;
 
 const IMMIX_BLOCK_ALIGNMENT: usize = IMMIX_BLOCK_SIZE;
 
+/// The metadata holds a byte per line, a byte for the whole block, then a bit
+/// per `OBJECT_ALIGNMENT` granule marking the objects that start there.
+/// The line and block bytes are made of the flags below.
+const IMMIX_OBJECT_MARKS: usize = IMMIX_USABLE_SIZE + IMMIX_LINES + 1;
+const IMMIX_OBJECT_MARK_BYTES: usize = IMMIX_USABLE_SIZE / OBJECT_ALIGNMENT / 8;
+const _: () = assert!(IMMIX_OBJECT_MARKS + IMMIX_OBJECT_MARK_BYTES <= IMMIX_BLOCK_SIZE);
+
 const MEDIUM_OBJECT_SIZE: usize = IMMIX_LINE_SIZE;
 
+/// Set on a line or block holding objects, or handed out to a mutator
+const LIVE: u8 = 1;
+/// Set on a line or block that held objects when the last collection ended.
+/// Object marks stick between nursery collections, so the objects on these
+/// lines are the old ones, and their lines stay live until a full collection.
+const OLD: u8 = 2;
+/// Set on a line or block by the collection under way as it marks objects
+/// there. Mutators go by `LIVE` until it is over, so they can keep allocating
+/// while marking is incremental.
+const MARKED: u8 = 4;
+
+/// Empty blocks kept after a collection for later allocations, unless
+/// configured otherwise with `set_free_block_budget`; the rest are released
+const DEFAULT_FREE_BLOCK_BUDGET: usize = 64;
+
+/// Large objects are collected once this many bytes of them, or as many as
+/// survived the last collection if that is more, have been allocated since
+const LARGE_OBJECT_MIN_TRIGGER: usize = 4 * 1024 * 1024;
+
+/// Written over freed lines and large objects when verifying the heap or
+/// stressing the collector. As a value it is a string at a non-canonical
+/// address, so following it faults.
+const POISON: usize = 0xdead_dead_dead_dead;
+
+/// Set to verify the heap around every collection; see `set_verify`
+const VERIFY_ENV_VAR: &str = "LISP_RS_VERIFY_HEAP";
+
+/// Set to a number N to collect on every Nth allocation; see `set_stress_gc`
+const STRESS_GC_ENV_VAR: &str = "LISP_RS_STRESS_GC";
+
+/// Every this many collections, unless configured otherwise with
+/// `set_full_collection_interval`, one traces the whole heap; the others only
+/// trace the objects allocated since the last one
+const DEFAULT_FULL_COLLECTION_INTERVAL: usize = 8;
+
+/// How many of the latest pauses `GcStats` keeps
+const PAUSE_HISTORY: usize = 64;
+
+/// An increment with a time budget reads the clock after tracing this many objects
+const PAUSE_CHECK_INTERVAL: usize = 64;
+
+/// Blocks with at most this many lines live after a collection, split into at
+/// least `EVACUATION_MIN_HOLES` holes, are fragmented enough to be worth evacuating
+const EVACUATION_MAX_LIVE_LINES: usize = IMMIX_LINES / 2;
+const EVACUATION_MIN_HOLES: usize = 4;
+/// Evacuating fewer blocks than this cannot free any
+const EVACUATION_MIN_CANDIDATES: usize = 2;
+
+/// Objects a marking thread holds before it offers half of them to the others
+const MARK_SHARE_MIN: usize = 64;
+
 #[repr(transparent)]
 #[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
 struct Block {
@@ -89,29 +153,119 @@ impl Block {
     }
 
     unsafe fn unchecked_line_live(&self, i: usize) -> bool {
-        *self.ptr.add(IMMIX_USABLE_SIZE + i) != 0
+        *self.ptr.add(IMMIX_USABLE_SIZE + i) & LIVE != 0
     }
 
     unsafe fn unchecked_set_line_live(&mut self, i: usize, live: bool) {
-        *self.ptr.add(IMMIX_USABLE_SIZE + i) = if live { 1 } else { 0 };
+        let flags = &mut *self.ptr.add(IMMIX_USABLE_SIZE + i);
+        *flags = if live { *flags | LIVE } else { *flags & !LIVE };
     }
 
     fn block_live(&self) -> bool {
-        unsafe { *self.ptr.add(IMMIX_USABLE_SIZE + IMMIX_LINES) != 0 }
+        unsafe { *self.ptr.add(IMMIX_USABLE_SIZE + IMMIX_LINES) & LIVE != 0 }
     }
 
     fn set_block_live(&mut self, live: bool) {
-        unsafe { *self.ptr.add(IMMIX_USABLE_SIZE + IMMIX_LINES) = if live { 1 } else { 0 } };
+        let flags = unsafe { &mut *self.ptr.add(IMMIX_USABLE_SIZE + IMMIX_LINES) };
+        *flags = if live { *flags | LIVE } else { *flags & !LIVE };
     }
 
-    fn reset_marks(&mut self) {
-        self.set_block_live(false);
+    // the line flags followed by the block flags
+    fn flags(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.add(IMMIX_USABLE_SIZE), IMMIX_LINES + 1) }
+    }
+
+    /// Whether the marks left by the last collection show a block mostly made of holes
+    fn fragmented(&self) -> bool {
+        if !self.block_live() {
+            return false;
+        }
+        let mut live_lines = 0;
+        let mut holes = 0;
+        for i in 0..IMMIX_LINES {
+            if unsafe { self.unchecked_line_live(i) } {
+                live_lines += 1;
+            } else if i == 0 || unsafe { self.unchecked_line_live(i - 1) } {
+                holes += 1;
+            }
+        }
+        live_lines <= EVACUATION_MAX_LIVE_LINES && holes >= EVACUATION_MIN_HOLES
+    }
+
+    /// Sets the mark bit of the object `offset` bytes into the block,
+    /// returning whether it was clear. Atomic, as the threads of a parallel
+    /// mark share the bytes of neighbouring objects.
+    unsafe fn unchecked_mark_object(&self, offset: usize) -> bool {
+        let granule = offset / OBJECT_ALIGNMENT;
+        let byte = unsafe { AtomicU8::from_ptr(self.ptr.add(IMMIX_OBJECT_MARKS + granule / 8)) };
+        let bit = 1 << (granule % 8);
+        byte.fetch_or(bit, Ordering::Relaxed) & bit == 0
+    }
+
+    /// Marks lines `start` to `end`, inclusive, and the block, atomically
+    unsafe fn unchecked_mark_lines(&self, start: usize, end: usize) {
+        for i in (start..=end).chain([IMMIX_LINES]) {
+            let flags = unsafe { AtomicU8::from_ptr(self.ptr.add(IMMIX_USABLE_SIZE + i)) };
+            flags.fetch_or(MARKED, Ordering::Relaxed);
+        }
+    }
+
+    unsafe fn unchecked_object_marked(&self, offset: usize) -> bool {
+        let granule = offset / OBJECT_ALIGNMENT;
+        unsafe { *self.ptr.add(IMMIX_OBJECT_MARKS + granule / 8) & 1 << (granule % 8) != 0 }
+    }
+
+    /// Overwrites the lines the last collection left unmarked with `POISON`
+    fn poison_free_lines(&mut self) {
         for i in 0..IMMIX_LINES {
-            unsafe { self.unchecked_set_line_live(i, false) };
+            if !unsafe { self.unchecked_line_live(i) } {
+                unsafe { poison(self.ptr.add(i * IMMIX_LINE_SIZE), IMMIX_LINE_SIZE) };
+            }
+        }
+    }
+
+    fn reset_marks(&mut self) {
+        unsafe {
+            self.ptr
+                .add(IMMIX_USABLE_SIZE)
+                .write_bytes(0, IMMIX_META_SIZE)
+        };
+    }
+
+    /// Clears the marks of the last collection for a new one to set, except
+    /// those of the old objects if it is a nursery collection
+    fn clear_marks(&mut self, full: bool) {
+        if full {
+            unsafe {
+                self.ptr
+                    .add(IMMIX_OBJECT_MARKS)
+                    .write_bytes(0, IMMIX_OBJECT_MARK_BYTES)
+            };
+        }
+        for flags in self.flags() {
+            *flags &= !MARKED;
+            if !full && *flags & OLD != 0 {
+                *flags |= MARKED;
+            }
+        }
+    }
+
+    /// Frees the lines left unmarked once marking is over, and makes the
+    /// objects on the others old
+    fn sweep_lines(&mut self) {
+        for flags in self.flags() {
+            *flags = if *flags & MARKED != 0 { LIVE | OLD } else { 0 };
         }
     }
 }
 
+unsafe fn poison(ptr: *mut u8, size: usize) {
+    let words = ptr as *mut usize;
+    for i in 0..size / size_of::<usize>() {
+        unsafe { words.add(i).write(POISON) };
+    }
+}
+
 #[derive(Debug, Clone)]
 struct BumpPointer {
     cursor: NonNull<u8>,
@@ -226,16 +380,343 @@ impl ImmixBlockHandler {
     }
 }
 
+/// Precedes every large object, padded so the object stays aligned
+#[repr(C)]
+struct LargeObjectHeader {
+    size: usize,
+    marked: bool,
+}
+
+const LARGE_OBJECT_HEADER_SIZE: usize =
+    size_of::<LargeObjectHeader>().div_ceil(OBJECT_ALIGNMENT) * OBJECT_ALIGNMENT;
+
+/// Objects too big for a block each get an allocation of their own, freed as
+/// soon as a collection finds them dead
+struct LargeObjectSpace {
+    objects: Vec<NonNull<LargeObjectHeader>>,
+    live_size: usize,
+    allocated_size: usize,
+}
+unsafe impl Send for LargeObjectSpace {}
+
+impl LargeObjectSpace {
+    fn new() -> Self {
+        LargeObjectSpace {
+            objects: Vec::new(),
+            live_size: 0,
+            allocated_size: 0,
+        }
+    }
+
+    fn layout(size: usize) -> Result<alloc::Layout, AllocError> {
+        alloc::Layout::from_size_align(LARGE_OBJECT_HEADER_SIZE + size, OBJECT_ALIGNMENT)
+            .map_err(|_| AllocError::InvalidInput)
+    }
+
+    /// Bytes held by large objects, including those not yet found dead
+    fn size(&self) -> usize {
+        self.live_size + self.allocated_size
+    }
+
+    fn wants_collection(&self) -> bool {
+        self.allocated_size >= LARGE_OBJECT_MIN_TRIGGER.max(self.live_size)
+    }
+
+    /// Allocates an object, already marked if marking is under way
+    fn alloc<T>(&mut self, size: usize, marked: bool) -> Result<NonNull<T>, AllocError> {
+        let layout = Self::layout(size)?;
+        let header = NonNull::new(unsafe { alloc::alloc(layout) } as *mut LargeObjectHeader)
+            .ok_or(AllocError::OutOfMemory)?;
+        unsafe { header.as_ptr().write(LargeObjectHeader { size, marked }) };
+        self.objects.push(header);
+        self.allocated_size += size;
+        Ok(unsafe { Self::object(header) })
+    }
+
+    unsafe fn header(obj: *mut u8) -> *mut LargeObjectHeader {
+        unsafe { obj.sub(LARGE_OBJECT_HEADER_SIZE) as *mut LargeObjectHeader }
+    }
+
+    unsafe fn object<T>(header: NonNull<LargeObjectHeader>) -> NonNull<T> {
+        unsafe {
+            NonNull::new_unchecked(
+                (header.as_ptr() as *mut u8).add(LARGE_OBJECT_HEADER_SIZE) as *mut T
+            )
+        }
+    }
+
+    /// Sets the mark of the large object at `obj`, returning whether it was clear
+    unsafe fn mark(obj: *mut u8) -> bool {
+        let marked = unsafe { AtomicBool::from_ptr(addr_of_mut!((*Self::header(obj)).marked)) };
+        !marked.swap(true, Ordering::Relaxed)
+    }
+
+    unsafe fn marked(obj: *mut u8) -> bool {
+        unsafe { (*Self::header(obj)).marked }
+    }
+
+    /// Clears the marks before a full collection. Nursery collections keep
+    /// them, so that the objects which survived earlier ones are left alone.
+    fn reset_marks(&mut self) {
+        for header in &self.objects {
+            unsafe { (*header.as_ptr()).marked = false };
+        }
+    }
+
+    /// Frees the objects left unmarked, poisoning them first if asked to
+    fn sweep(&mut self, poison_freed: bool) {
+        let mut live_size = 0;
+        self.objects.retain(|header| unsafe {
+            let LargeObjectHeader { size, marked } = *header.as_ptr();
+            if marked {
+                live_size += size;
+            } else {
+                if poison_freed {
+                    poison(Self::object::<u8>(*header).as_ptr(), size);
+                }
+                alloc::dealloc(header.as_ptr() as *mut u8, Self::layout(size).unwrap());
+            }
+            marked
+        });
+        self.live_size = live_size;
+        self.allocated_size = 0;
+    }
+}
+
+/// How many blocks the heap holds, as of the last collection for the blocks
+/// no mutator owns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockCounts {
+    pub in_use: usize,
+    pub free: usize,
+    pub large_objects: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    /// Collections that traced the whole heap rather than the nursery
+    pub full_collections: usize,
+    /// Pauses in which an incremental collection started, marked or finished
+    pub increments: usize,
+    pub total_pause: Duration,
+    pub max_pause: Duration,
+    pub last_pause: Duration,
+    /// The latest pauses, oldest first, whether they were whole collections or increments
+    pub recent_pauses: VecDeque<Duration>,
+    /// Bytes allocated since the allocator was created, including objects since freed
+    pub bytes_allocated: usize,
+    /// Bytes of the objects the last collection found reachable, counting
+    /// every object that survived the collections since the last full one
+    pub live_bytes: usize,
+    pub blocks: BlockCounts,
+    /// Blocks the last full collection found fragmented enough to evacuate
+    pub fragmented_blocks: usize,
+    /// Symbols interned as of the last collection
+    pub symbols: usize,
+}
+
+/// A collection whose marking is under way
+struct Cycle {
+    full: bool,
+    live_bytes: usize,
+}
+
 pub struct GlobalImmixAllocator {
     blocks: Mutex<Vec<Block>>, // TODO: sort by free size?
     local_lists: Mutex<Vec<Arc<Mutex<ImmixMutatorState>>>>,
+    // shared with the mutators, which record and look up positions without
+    // the allocator lock
+    source_map: Arc<Mutex<SourceMap>>,
+    // blocks the last collection found fragmented, unless it was a nursery
+    // collection, whose marks keep the old objects that have since died
+    fragmented_blocks: Option<usize>,
+    // kept between collections so tracing does not allocate
+    mark_stack: Vec<PackedPtr>,
+    large_objects: LargeObjectSpace,
+    free_block_budget: usize,
+    // blocks currently allocated from the OS
+    heap_blocks: usize,
+    max_heap_size: Option<usize>,
+    // swept after marking, if the symbols of this heap are held weakly
+    symbols: Option<Arc<Mutex<Arena>>>,
+    verify: bool,
+    poison: bool,
+    full_collection_interval: usize,
+    collections_until_full: usize,
+    // old objects written by mutators that have since gone, which still point
+    // at young objects; see `ImmixMutator::write_barrier`
+    remembered: Vec<PackedPtr>,
+    // objects traced per increment, when collections are incremental
+    increment_budget: Option<usize>,
+    // and the longest an increment may trace for, if limited
+    increment_pause: Option<Duration>,
+    cycle: Option<Cycle>,
+    // values overwritten by mutators that have since gone, for marking to trace
+    satb: Vec<PackedPtr>,
+    // the other threads of a parallel mark, if tracing is parallel
+    mark_pool: Option<MarkPool>,
+    stats: GcStats,
+    safepoint: Arc<Safepoint>,
 }
 
+// the blocks and mutator states it points into are only touched under its lock,
+// or by a collection once the other mutators are stopped
+unsafe impl Send for GlobalImmixAllocator {}
+
 impl GlobalImmixAllocator {
     pub fn new() -> Self {
         GlobalImmixAllocator {
             blocks: Mutex::new(Vec::new()),
             local_lists: Mutex::new(Vec::new()),
+            source_map: Arc::new(Mutex::new(SourceMap::default())),
+            fragmented_blocks: Some(0),
+            mark_stack: Vec::new(),
+            large_objects: LargeObjectSpace::new(),
+            free_block_budget: DEFAULT_FREE_BLOCK_BUDGET,
+            heap_blocks: 0,
+            max_heap_size: None,
+            symbols: None,
+            verify: std::env::var_os(VERIFY_ENV_VAR).is_some(),
+            poison: false,
+            full_collection_interval: DEFAULT_FULL_COLLECTION_INTERVAL,
+            collections_until_full: 0,
+            remembered: Vec::new(),
+            increment_budget: None,
+            increment_pause: None,
+            cycle: None,
+            satb: Vec::new(),
+            mark_pool: None,
+            stats: GcStats::default(),
+            safepoint: Arc::new(Safepoint::new()),
+        }
+    }
+
+    /// An allocator whose collections free the symbols of `symbols` they cannot reach
+    pub fn with_symbols(symbols: Arc<Mutex<Arena>>) -> Self {
+        GlobalImmixAllocator {
+            symbols: Some(symbols),
+            ..Self::new()
+        }
+    }
+
+    /// Sets how many empty blocks collections keep instead of returning them to the OS
+    pub fn set_free_block_budget(&mut self, blocks: usize) {
+        self.free_block_budget = blocks;
+    }
+
+    /// Whether every collection checks the heap reachable from the roots before
+    /// and after it runs, and poisons the memory it frees. Off unless the
+    /// `LISP_RS_VERIFY_HEAP` environment variable is set.
+    ///
+    /// Verification marks objects as they are allocated, which would make them
+    /// old, so every collection is full while it is on.
+    pub fn set_verify(&mut self, verify: bool) {
+        if verify != self.verify {
+            // leaves every object allocated so far marked, as verification
+            // expects, and after it only the objects that survived
+            self.collect(true, false);
+        }
+        self.verify = verify;
+        for l in self.local_lists.lock().unwrap().iter() {
+            l.lock().unwrap().verify = verify;
+        }
+    }
+
+    /// Whether collections overwrite the lines and large objects they free, so
+    /// that reading a freed object gives garbage rather than its old value.
+    /// Verifying the heap poisons too.
+    pub fn set_poison(&mut self, poison: bool) {
+        self.poison = poison;
+    }
+
+    /// Makes every `interval`th collection full, tracing the whole heap, and the
+    /// others nursery collections, which only trace the objects allocated since
+    /// the last collection and the old objects the write barrier recorded.
+    /// Garbage that survived a collection is only freed by the next full one.
+    /// An interval of 1 makes every collection full.
+    pub fn set_full_collection_interval(&mut self, interval: usize) {
+        assert!(interval > 0, "full collection interval must be positive");
+        self.full_collection_interval = interval;
+        self.collections_until_full = self.collections_until_full.min(interval - 1);
+    }
+
+    /// Makes the collections that allocations start incremental: starting one
+    /// only marks the roots, and marking then goes on in pauses of at most
+    /// `budget` objects each, one whenever a mutator runs out of room in its
+    /// current hole, until the last pause frees what it could not reach. A
+    /// collection started while one is under way finishes it instead.
+    /// Verifying the heap keeps collections whole.
+    pub fn set_incremental(&mut self, budget: Option<usize>) {
+        assert!(budget != Some(0), "increment budget must be positive");
+        self.increment_budget = budget;
+    }
+
+    /// Also ends each increment of incremental marking once it has traced for
+    /// `pause`, if that comes before its object budget. Starting a collection
+    /// marks the roots in a pause of its own, and the increment that finishes
+    /// marking frees what it could not reach too, so those two may run longer.
+    pub fn set_increment_pause(&mut self, pause: Option<Duration>) {
+        self.increment_pause = pause;
+    }
+
+    /// Spreads the tracing of each collection over `threads` threads, the
+    /// collecting thread among them, which steal objects from each other's mark
+    /// stacks as they run out. The others are started here and park between
+    /// collections. Increments of incremental marking, and collections that
+    /// evacuate, still trace on the collecting thread alone. Tracing is serial
+    /// by default.
+    pub fn set_mark_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "mark thread count must be positive");
+        self.mark_pool = (threads > 1).then(|| MarkPool::new(threads));
+    }
+
+    /// Limits the bytes of blocks and large objects the heap may hold; allocations
+    /// that would exceed it fail with `OutOfMemory` once a collection cannot help
+    pub fn set_max_heap_size(&mut self, bytes: Option<usize>) {
+        self.max_heap_size = bytes;
+    }
+
+    fn has_room(&self, size: usize) -> bool {
+        self.max_heap_size
+            .is_none_or(|max| self.heap_size().saturating_add(size) <= max)
+    }
+
+    fn heap_size(&self) -> usize {
+        self.heap_blocks * IMMIX_BLOCK_SIZE + self.large_objects.size()
+    }
+
+    pub fn block_counts(&self) -> BlockCounts {
+        let blocks = self.blocks.lock().unwrap();
+        let free = blocks.iter().filter(|b| !b.block_live()).count();
+        let owned: usize = self
+            .local_lists
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|l| l.lock().unwrap().blocks.base().len())
+            .sum();
+        BlockCounts {
+            in_use: blocks.len() - free + owned,
+            free,
+            large_objects: self.large_objects.objects.len(),
+        }
+    }
+
+    pub fn stats(&self) -> GcStats {
+        let pending: usize = self
+            .local_lists
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|l| l.lock().unwrap().allocated)
+            .sum();
+        GcStats {
+            bytes_allocated: self.stats.bytes_allocated
+                + pending
+                + self.large_objects.allocated_size,
+            blocks: self.block_counts(),
+            ..self.stats.clone()
         }
     }
 
@@ -277,11 +758,13 @@ impl GlobalImmixAllocator {
             blocks.remove(i);
             Ok(bh)
         } else if gc_on_fail {
-            drop(blocks);
-            self.gc();
             Err(AllocError::GcTryAgain)
+        } else if self.has_room(IMMIX_BLOCK_SIZE) {
+            let bh = ImmixBlockHandler::new()?;
+            self.heap_blocks += 1;
+            Ok(bh)
         } else {
-            ImmixBlockHandler::new()
+            Err(AllocError::OutOfMemory)
         }
     }
 
@@ -323,72 +806,394 @@ impl GlobalImmixAllocator {
     }
 
     unsafe fn mark_ptr(ptr: *mut u8, size: usize) {
-        let (mut start_block, start_line, _) = unsafe { Block::block_from_ptr(ptr) };
+        let (start_block, start_line, _) = unsafe { Block::block_from_ptr(ptr) };
         let (end_block, end_line, _) = unsafe { Block::block_from_ptr(ptr.add(size - 1)) };
 
         assert!(start_block == end_block);
         assert!(end_line < IMMIX_LINES);
-        for line in start_line..=end_line {
-            unsafe {
-                start_block.unchecked_set_line_live(line, true);
+        unsafe { start_block.unchecked_mark_lines(start_line, end_line) };
+    }
+
+    /// Marks an object as it is allocated, with its lines
+    unsafe fn mark_new(ptr: *mut u8, size: usize) {
+        let (block, line, offset) = unsafe { Block::block_from_ptr(ptr) };
+        unsafe {
+            block.unchecked_mark_object(line * IMMIX_LINE_SIZE + offset);
+            Self::mark_ptr(ptr, size);
+        }
+    }
+
+    /// Marks `obj` and the lines it covers, returning whether its fields still
+    /// have to be traced. Counts the bytes of newly marked objects into `live_bytes`.
+    unsafe fn mark(obj: PackedPtr, live_bytes: &mut usize) -> bool {
+        if let UnpackedPtr::Symbol(sym) = obj.unpack() {
+            unsafe { Arena::mark(sym) };
+            return false;
+        }
+        let Some((ptr, size)) = obj.heap_ptr() else {
+            return false;
+        };
+        let ptr = ptr.as_ptr();
+        if size > IMMIX_USABLE_SIZE {
+            if !unsafe { LargeObjectSpace::mark(ptr) } {
+                return false;
+            }
+        } else {
+            let (block, line, offset) = unsafe { Block::block_from_ptr(ptr) };
+            if !unsafe { block.unchecked_mark_object(line * IMMIX_LINE_SIZE + offset) } {
+                return false;
             }
-            start_block.set_block_live(true);
+            unsafe { Self::mark_ptr(ptr, size) };
         }
+        *live_bytes += size;
+        true
     }
 
+    /// Whether the last collection found `obj` live. Values outside the heap always are.
+    unsafe fn marked(obj: PackedPtr) -> bool {
+        let Some((ptr, size)) = obj.heap_ptr() else {
+            return true;
+        };
+        if size > IMMIX_USABLE_SIZE {
+            return unsafe { LargeObjectSpace::marked(ptr.as_ptr()) };
+        }
+        let (block, line, offset) = unsafe { Block::block_from_ptr(ptr.as_ptr()) };
+        unsafe { block.unchecked_object_marked(line * IMMIX_LINE_SIZE + offset) }
+    }
+
+    /// Collects on the current thread, tracing the whole heap only if a full
+    /// collection is due; see `set_full_collection_interval`. Mutators on
+    /// other threads must be stopped; `ImmixMutator::gc` stops them first.
     pub fn gc(&mut self) {
-        // println!("GC");
+        if self.cycle.is_some() {
+            self.finish_incremental();
+        }
+        self.collect(self.full_collection_due(), false)
+    }
+
+    /// Collects, tracing the whole heap. Mutators on other threads must be stopped.
+    pub fn full_gc(&mut self) {
+        self.collect(true, false)
+    }
+
+    /// Whether the last collection was a full one that found enough
+    /// fragmented blocks for `defragment` to evacuate
+    pub fn fragmented(&self) -> bool {
+        self.fragmented_blocks
+            .is_some_and(|blocks| blocks >= EVACUATION_MIN_CANDIDATES)
+    }
+
+    /// Collects, evacuating the conses out of fragmented blocks, if the last
+    /// full collection found enough of those blocks. Returns whether it did.
+    /// If the last collection was a nursery collection, a full one runs first
+    /// to find them.
+    ///
+    /// Unsafe because objects move: every reference into the heap, in every
+    /// mutator, must be held in a root slot. Collections triggered by
+    /// allocation never move objects, since builtins hold unrooted pointers
+    /// into rooted structures across allocations.
+    pub unsafe fn defragment(&mut self) -> bool {
+        if self.fragmented_blocks.is_none() {
+            self.full_gc();
+        }
+        if self.fragmented_blocks.unwrap_or(0) < EVACUATION_MIN_CANDIDATES {
+            return false;
+        }
+        self.collect(true, true);
+        true
+    }
+
+    /// Marks from the roots and frees what it could not reach, once it has
+    /// finished any incremental collection under way. A nursery collection,
+    /// when `full` is false, keeps the marks of the last one, so tracing stops
+    /// at the old objects it marked; the old objects written since are traced
+    /// as well, for the young objects they point at. Only full collections
+    /// may evacuate.
+    fn collect(&mut self, full: bool, evacuate: bool) {
+        debug_assert!(full || !evacuate);
+        if self.cycle.is_some() {
+            self.finish_incremental();
+        }
+        if self.verify {
+            self.verify_heap("before");
+        }
+        let start = Instant::now();
+        let mut evacuator = self.start_marking(full, evacuate);
+        self.trace(None, None, &mut evacuator);
+        self.sweep(evacuator);
+        self.record_pause(start.elapsed());
+
+        if self.verify {
+            self.verify_heap("after");
+        }
+    }
+
+    fn full_collection_due(&self) -> bool {
+        self.verify || self.collections_until_full == 0
+    }
+
+    /// Starts an incremental collection, marking the roots, unless one is
+    /// under way already. Mutators on other threads must be stopped.
+    pub fn start_incremental(&mut self) {
+        if self.cycle.is_some() {
+            return;
+        }
+        let start = Instant::now();
+        self.start_marking(self.full_collection_due(), false);
+        self.stats.increments += 1;
+        self.record_pause(start.elapsed());
+    }
+
+    /// Marks as many objects as an increment may for the incremental
+    /// collection under way, if there is one, and finishes it once everything
+    /// is marked. Mutators on other threads must be stopped.
+    pub fn step_incremental(&mut self) {
+        if self.cycle.is_none() {
+            return;
+        }
+        let start = Instant::now();
+        let deadline = self.increment_pause.map(|pause| start + pause);
+        if self.trace(self.increment_budget, deadline, &mut None) {
+            self.sweep(None);
+        }
+        self.stats.increments += 1;
+        self.record_pause(start.elapsed());
+    }
+
+    /// Finishes the incremental collection under way in one pause
+    fn finish_incremental(&mut self) {
+        let start = Instant::now();
+        self.trace(None, None, &mut None);
+        self.sweep(None);
+        self.stats.increments += 1;
+        self.record_pause(start.elapsed());
+    }
+
+    /// Clears the marks of the last collection and marks the roots, leaving
+    /// the objects they reach to be traced. Returns the evacuator for a
+    /// collection that evacuates.
+    fn start_marking(&mut self, full: bool, evacuate: bool) -> Option<Evacuator> {
+        let room = self
+            .max_heap_size
+            .map_or(usize::MAX, |max| max.saturating_sub(self.heap_size()));
         let mut global_blocks = self.blocks.lock().unwrap();
         let locals = self.local_lists.lock().unwrap();
         let mut multilock = unsafe { Self::lock_all_lists(&locals) };
 
-        let mut stack = vec![];
-        let mut seen = HashSet::new();
+        let stack = &mut self.mark_stack;
+        let mut live_bytes = 0;
+
+        // candidates are chosen from the marks of the last collection, so
+        // this has to happen before they are reset
+        let candidates = if evacuate {
+            let mut candidates: Vec<Block> = global_blocks
+                .iter()
+                .filter(|b| b.fragmented())
+                .cloned()
+                .collect();
+            for l in multilock.iter() {
+                candidates.extend(
+                    l.blocks
+                        .base()
+                        .iter()
+                        .filter(|b| **b != l.head.block && b.fragmented())
+                        .cloned(),
+                );
+            }
+            Some(candidates)
+        } else {
+            None
+        };
 
         for b in global_blocks.iter_mut() {
-            b.reset_marks();
+            b.clear_marks(full);
         }
 
         for l in multilock.iter_mut() {
             for b in unsafe { l.blocks.base_mut().iter_mut() } {
-                b.reset_marks();
+                b.clear_marks(full);
             }
+        }
+        if full {
+            self.large_objects.reset_marks();
+        }
+
+        // the empty blocks are copied into first, and new ones only allocated
+        // while the heap has room for them
+        let mut evacuator = candidates.map(|candidates| {
+            let mut free = vec![];
+            global_blocks.retain(|b| {
+                if b.block_live() {
+                    true
+                } else {
+                    free.push(b.clone());
+                    false
+                }
+            });
+            Evacuator::new(candidates, free, room / IMMIX_BLOCK_SIZE)
+        });
 
+        // roots may point into the blocks of other mutators, so marking only
+        // starts once every block has been reset
+        for l in multilock.iter_mut() {
+            l.marking = true;
             for r in l.roots.cursor() {
-                if seen.insert(r.ptr()) {
-                    stack.push(r.ptr());
+                let mut ptr = r.ptr();
+                if let Some(evacuator) = &mut evacuator {
+                    ptr = unsafe { evacuator.evacuate(ptr) };
+                    r.set_ptr(ptr);
+                }
+                if unsafe { Self::mark(ptr, &mut live_bytes) } {
+                    stack.push(ptr);
                 }
             }
         }
 
-        // println!("unique roots: {}", seen.len());
-        let mut used_space = 0usize;
+        // the old objects are all marked, so a full collection reaches
+        // whatever they point at anyway
+        let mut remembered = take(&mut self.remembered);
+        for l in multilock.iter_mut() {
+            remembered.append(&mut l.remembered);
+        }
+        if !full {
+            for obj in &remembered {
+                if let Some((first, rest)) = obj.obj_ptrs() {
+                    for inner_obj in [first, rest] {
+                        if unsafe { Self::mark(inner_obj, &mut live_bytes) } {
+                            stack.push(inner_obj);
+                        }
+                    }
+                }
+            }
+        }
+        remembered.clear();
+        self.remembered = remembered;
 
-        while let Some(obj) = stack.pop() {
-            for (ptr, size) in obj.heap_ptrs() {
-                unsafe { Self::mark_ptr(ptr.as_ptr() as *mut u8, size) };
-                used_space += size;
+        self.cycle = Some(Cycle { full, live_bytes });
+        evacuator
+    }
+
+    /// Traces up to `budget` objects, or all of them, from the mark stack
+    /// and what mutators have overwritten since the last call, stopping early
+    /// if `deadline` passes. Returns whether marking is done.
+    fn trace(
+        &mut self,
+        budget: Option<usize>,
+        deadline: Option<Instant>,
+        evacuator: &mut Option<Evacuator>,
+    ) -> bool {
+        let cycle = self.cycle.as_mut().expect("no collection under way");
+        let stack = &mut self.mark_stack;
+
+        let locals = self.local_lists.lock().unwrap();
+        let overwritten = locals.iter().map(|l| take(&mut l.lock().unwrap().satb));
+        for obj in overwritten.chain([take(&mut self.satb)]).flatten() {
+            if unsafe { Self::mark(obj, &mut cycle.live_bytes) } {
+                stack.push(obj);
             }
+        }
+        drop(locals);
 
-            for inner_obj in obj.obj_ptrs() {
-                if seen.insert(inner_obj) {
-                    stack.push(inner_obj);
+        if budget.is_none() && evacuator.is_none() {
+            if let Some(pool) = &self.mark_pool {
+                cycle.live_bytes += pool.trace(stack);
+                return true;
+            }
+        }
+
+        let mut traced = 0;
+        while budget.is_none_or(|budget| traced < budget) {
+            let Some(obj) = stack.pop() else {
+                break;
+            };
+            traced += 1;
+
+            if let Some(evacuator) = evacuator {
+                unsafe { evacuator.evacuate_fields(obj) };
+            }
+
+            if let Some((first, rest)) = obj.obj_ptrs() {
+                for inner_obj in [first, rest] {
+                    if unsafe { Self::mark(inner_obj, &mut cycle.live_bytes) } {
+                        stack.push(inner_obj);
+                    }
                 }
             }
+
+            if traced % PAUSE_CHECK_INTERVAL == 0
+                && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+        }
+        stack.is_empty()
+    }
+
+    /// Frees what marking did not reach, once it is done
+    fn sweep(&mut self, evacuator: Option<Evacuator>) {
+        let Cycle {
+            full,
+            mut live_bytes,
+        } = self.cycle.take().expect("no collection under way");
+        let mut global_blocks = self.blocks.lock().unwrap();
+        let locals = self.local_lists.lock().unwrap();
+        let mut multilock = unsafe { Self::lock_all_lists(&locals) };
+
+        self.source_map.lock().unwrap().relocate(|ptr| {
+            let ptr = match &evacuator {
+                Some(evacuator) => unsafe { evacuator.forwarded(ptr) },
+                None => ptr,
+            };
+            if unsafe { Self::marked(ptr) } {
+                Some(ptr)
+            } else {
+                None
+            }
+        });
+
+        let mut allocated = self.large_objects.allocated_size;
+        let poison = self.verify || self.poison;
+        self.large_objects.sweep(poison);
+        // old objects are not traced by nursery collections, so the symbols
+        // only they refer to go unmarked until the next full one
+        match &self.symbols {
+            Some(symbols) if full => {
+                let mut symbols = symbols.lock().unwrap();
+                symbols.sweep();
+                self.stats.symbols = symbols.len();
+            }
+            _ => (),
         }
 
-        // println!("seen: {}", seen.len());
+        let evacuated = evacuator.is_some();
+        if let Some(evacuator) = evacuator {
+            self.heap_blocks += evacuator.new_blocks;
+            global_blocks.extend(evacuator.targets);
+            global_blocks.extend(evacuator.free);
+        }
 
-        let mut live_blocks = global_blocks.iter().filter(|b| b.block_live()).count();
+        for b in global_blocks.iter_mut() {
+            b.sweep_lines();
+        }
+        let mut fragmented_blocks = global_blocks.iter().filter(|b| b.fragmented()).count();
 
         let mut dead_blocks = vec![];
         for l in multilock.iter_mut() {
+            l.marking = false;
+            for b in unsafe { l.blocks.base_mut().iter_mut() } {
+                b.sweep_lines();
+            }
+            // the rest of the head is handed out again, though nothing there is old
             l.head.mark_bump_range();
             l.start_recycle = true;
+            allocated += take(&mut l.allocated);
 
+            let head = l.head.block.clone();
             unsafe { l.blocks.base_mut() }.retain(|b| {
                 if b.block_live() {
-                    live_blocks += 1;
+                    if *b != head && b.fragmented() {
+                        fragmented_blocks += 1;
+                    }
                     true
                 } else {
                     dead_blocks.push(b.clone());
@@ -396,39 +1201,565 @@ impl GlobalImmixAllocator {
                 }
             });
         }
-
-        let total_space = live_blocks * IMMIX_BLOCK_SIZE;
-        // println!("live blocks: {live_blocks}");
-        // println!("total space: {total_space}");
-        // println!("utilized space: {used_space}");
-        // println!("efficiency: {}", used_space as f64 / total_space as f64);
+        global_blocks.retain(|b| {
+            if b.block_live() {
+                true
+            } else {
+                dead_blocks.push(b.clone());
+                false
+            }
+        });
+
+        if poison {
+            let local_blocks = multilock
+                .iter_mut()
+                .flat_map(|l| unsafe { l.blocks.base_mut() }.iter_mut());
+            for b in global_blocks
+                .iter_mut()
+                .chain(local_blocks)
+                .chain(dead_blocks.iter_mut())
+            {
+                b.poison_free_lines();
+            }
+        }
 
         drop(multilock);
         drop(global_blocks);
         drop(locals);
 
+        // the blocks evacuated into are sparse, so only a collection that
+        // did not move anything can ask for another evacuation
+        self.fragmented_blocks = if !full {
+            None
+        } else if evacuated {
+            Some(0)
+        } else {
+            Some(fragmented_blocks)
+        };
+
+        let surplus = dead_blocks.len().saturating_sub(self.free_block_budget);
+        for mut b in dead_blocks.drain(..surplus) {
+            unsafe { b.deallocate() };
+        }
+        self.heap_blocks -= surplus;
         self.return_blocks(dead_blocks);
-    }
-}
 
-struct ImmixMutatorState {
-    head: ImmixBlockHandler,
-    blocks: SortedVec<Block>,
-    roots: RootList,
-    start_recycle: bool,
-}
+        self.collections_until_full = if full {
+            self.full_collection_interval - 1
+        } else {
+            self.collections_until_full - 1
+        };
 
-impl ImmixMutatorState {
-    fn find_hole<'a, I: Iterator<Item = &'a Block>>(
-        iter: I,
-        size: usize,
-    ) -> Option<ImmixBlockHandler> {
-        for b in iter {
-            if let Some(bh) = ImmixBlockHandler::from_block(b.clone(), size, 0) {
-                return Some(bh);
-            }
+        let stats = &mut self.stats;
+        stats.collections += 1;
+        if full {
+            stats.full_collections += 1;
+            stats.fragmented_blocks = fragmented_blocks;
+        } else {
+            live_bytes += stats.live_bytes;
         }
-        None
+        stats.bytes_allocated += allocated;
+        stats.live_bytes = live_bytes;
+    }
+
+    fn record_pause(&mut self, pause: Duration) {
+        let stats = &mut self.stats;
+        stats.total_pause += pause;
+        stats.max_pause = stats.max_pause.max(pause);
+        stats.last_pause = pause;
+        if stats.recent_pauses.len() == PAUSE_HISTORY {
+            stats.recent_pauses.pop_front();
+        }
+        stats.recent_pauses.push_back(pause);
+    }
+
+    /// Checks everything reachable from the roots; see `HeapVerifier`
+    fn verify_heap(&self, phase: &str) {
+        let global_blocks = self.blocks.lock().unwrap();
+        let locals = self.local_lists.lock().unwrap();
+        let multilock = unsafe { Self::lock_all_lists(&locals) };
+
+        let local_blocks = multilock.iter().flat_map(|l| l.blocks.base().iter());
+        let mut verifier = HeapVerifier {
+            blocks: global_blocks
+                .iter()
+                .chain(local_blocks)
+                .map(|b| b.ptr as usize)
+                .collect(),
+            large_objects: self
+                .large_objects
+                .objects
+                .iter()
+                .map(|h| unsafe { LargeObjectSpace::object::<u8>(*h) }.as_ptr() as usize)
+                .collect(),
+            symbols: self.symbols.as_ref().map(|symbols| {
+                let symbols = symbols.lock().unwrap();
+                symbols.symbols().map(|sym| sym.as_ptr() as usize).collect()
+            }),
+            seen: HashSet::new(),
+        };
+
+        let roots = multilock.iter().flat_map(|l| l.roots.cursor());
+        for (i, r) in roots.enumerate() {
+            verifier.verify(phase, i, r.ptr());
+        }
+    }
+}
+
+/// Walks the values reachable from the roots, checking that each is well
+/// formed and that each pointer lands on a marked line of a known block or on
+/// a large object, so that rooting bugs are reported where they first show
+struct HeapVerifier {
+    blocks: HashSet<usize>,
+    large_objects: HashSet<usize>,
+    symbols: Option<HashSet<usize>>,
+    seen: HashSet<usize>,
+}
+
+impl HeapVerifier {
+    fn verify(&mut self, phase: &str, root: usize, value: PackedPtr) {
+        let mut stack = vec![(value, 0)];
+        while let Some((value, depth)) = stack.pop() {
+            if let Err(err) = self.check(value) {
+                panic!(
+                    "heap verification failed {} collection: {:#x}, {} fields below root {}, {}",
+                    phase,
+                    value.raw(),
+                    depth,
+                    root,
+                    err
+                );
+            }
+            if value.heap_ptr().is_some() && self.seen.insert(value.raw() & !7) {
+                if let Some((first, rest)) = value.obj_ptrs() {
+                    stack.push((first, depth + 1));
+                    stack.push((rest, depth + 1));
+                }
+            }
+        }
+    }
+
+    fn check(&self, value: PackedPtr) -> Result<(), String> {
+        let raw = value.raw();
+        if raw == POISON {
+            return Err("holds the poison written over freed memory".into());
+        }
+        let addr = raw & !7;
+        match value.tag_type() {
+            TagType::Integer | TagType::Nil | TagType::Function => return Ok(()),
+            TagType::Symbol => {
+                return match &self.symbols {
+                    Some(symbols) if !symbols.contains(&addr) => {
+                        Err("is a symbol that was freed".into())
+                    }
+                    _ => Ok(()),
+                };
+            }
+            _ => (),
+        }
+        if self.large_objects.contains(&addr) {
+            return Ok(());
+        }
+
+        let block = addr / IMMIX_BLOCK_ALIGNMENT * IMMIX_BLOCK_ALIGNMENT;
+        if !self.blocks.contains(&block) {
+            return Err("points outside the heap".into());
+        }
+        let offset = addr - block;
+        if offset >= IMMIX_USABLE_SIZE {
+            return Err("points into block metadata".into());
+        }
+        let block = Block {
+            ptr: block as *mut u8,
+        };
+        let line = offset / IMMIX_LINE_SIZE;
+        if !block.line_live(line) {
+            return Err(format!("points into free line {} of {:?}", line, block));
+        }
+
+        let header = unsafe { *(addr as *const usize) };
+        if header == POISON {
+            return Err("points at a poisoned object".into());
+        }
+        if value.tag_type() == TagType::Boxed
+            && header != BoxKind::Float as usize
+            && header != BoxKind::Bigint as usize
+        {
+            return Err(format!("points at a box of unknown kind {:#x}", header));
+        }
+        if let UnpackedPtr::Cons(ptr) | UnpackedPtr::Object(ptr) = value.unpack() {
+            if unsafe { ptr.as_ref() }.forwarded().is_some() {
+                return Err("points at a cons that was evacuated".into());
+            }
+        }
+
+        let (_, size) = value.heap_ptr().unwrap();
+        let end_line = (offset + size - 1) / IMMIX_LINE_SIZE;
+        if end_line >= IMMIX_LINES {
+            return Err("runs past the end of its block".into());
+        }
+        if let Some(line) = (line..=end_line).find(|&l| !block.line_live(l)) {
+            return Err(format!("ends in free line {} of {:?}", line, block));
+        }
+        // objects are marked when allocated while verifying, so an unmarked
+        // one was freed, even if its line has since been reused
+        if !unsafe { block.unchecked_object_marked(offset) } {
+            return Err("points at an object that was freed".into());
+        }
+        Ok(())
+    }
+}
+
+/// The threads of a parallel mark besides the collecting one. They are
+/// started once, by `set_mark_threads`, and park between collections.
+struct MarkPool {
+    stacks: Arc<MarkStacks>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+impl MarkPool {
+    fn new(threads: usize) -> Self {
+        let stacks = Arc::new(MarkStacks {
+            shared: (0..threads).map(|_| Mutex::new(Vec::new())).collect(),
+            state: Mutex::new(MarkState::default()),
+            wake: Condvar::new(),
+            finished: Condvar::new(),
+            waiting: AtomicUsize::new(0),
+        });
+        let workers = (1..threads)
+            .map(|id| {
+                let stacks = stacks.clone();
+                std::thread::Builder::new()
+                    .name(format!("mark-{}", id))
+                    .spawn(move || stacks.run_worker(id))
+                    .expect("cannot start mark thread")
+            })
+            .collect();
+        MarkPool { stacks, workers }
+    }
+
+    /// Marks everything reachable from the objects on `stack`, which are
+    /// marked already, on every thread of the pool. Returns the bytes of the
+    /// objects it marked, and leaves `stack` empty.
+    fn trace(&self, stack: &mut Vec<PackedPtr>) -> usize {
+        let stacks = &self.stacks;
+        let threads = stacks.shared.len();
+        let chunk = stack.len().div_ceil(threads).max(1);
+        for (shared, objs) in stacks.shared.iter().zip(stack.chunks(chunk)) {
+            shared.lock().unwrap().extend_from_slice(objs);
+        }
+        stack.clear();
+
+        let mut state = stacks.state.lock().unwrap();
+        state.trace += 1;
+        state.active = threads;
+        state.running = threads - 1;
+        state.live_bytes = 0;
+        stacks.wake.notify_all();
+        drop(state);
+
+        let live_bytes = stacks.work(0);
+        let mut state = stacks.state.lock().unwrap();
+        while state.running > 0 {
+            state = stacks.finished.wait(state).unwrap();
+        }
+        live_bytes + state.live_bytes
+    }
+}
+
+impl Drop for MarkPool {
+    fn drop(&mut self) {
+        self.stacks.state.lock().unwrap().shutdown = true;
+        self.stacks.wake.notify_all();
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+/// The mark stacks of a parallel mark. Each thread traces from a stack of its
+/// own, and moves half of it to its shared stack whenever that is empty, for
+/// the threads that run out of objects to steal.
+struct MarkStacks {
+    shared: Vec<Mutex<Vec<PackedPtr>>>,
+    state: Mutex<MarkState>,
+    // workers wait here for a trace to start, and idle threads for objects
+    // to steal or for the trace to end
+    wake: Condvar,
+    // the collecting thread waits here for the workers to finish a trace
+    finished: Condvar,
+    // idle threads waiting on `wake`, for a thread that shares to wake them
+    waiting: AtomicUsize,
+}
+
+#[derive(Default)]
+struct MarkState {
+    // counts the traces started, so that a worker can tell a new one
+    trace: usize,
+    // threads with objects to trace; tracing is over once none have
+    active: usize,
+    // workers yet to finish the trace under way
+    running: usize,
+    // what the workers marked in it
+    live_bytes: usize,
+    shutdown: bool,
+}
+
+// the objects on the stacks are only touched while the mutators are stopped
+unsafe impl Send for MarkStacks {}
+unsafe impl Sync for MarkStacks {}
+
+impl MarkStacks {
+    fn run_worker(&self, id: usize) {
+        let mut traced = 0;
+        loop {
+            let mut state = self.state.lock().unwrap();
+            while state.trace == traced && !state.shutdown {
+                state = self.wake.wait(state).unwrap();
+            }
+            if state.shutdown {
+                return;
+            }
+            traced = state.trace;
+            drop(state);
+
+            let live_bytes = self.work(id);
+            let mut state = self.state.lock().unwrap();
+            state.live_bytes += live_bytes;
+            state.running -= 1;
+            if state.running == 0 {
+                self.finished.notify_one();
+            }
+        }
+    }
+
+    fn work(&self, id: usize) -> usize {
+        let mut stack: Vec<PackedPtr> = Vec::new();
+        let mut live_bytes = 0;
+        loop {
+            while let Some(obj) = stack.pop() {
+                if let Some((first, rest)) = obj.obj_ptrs() {
+                    for inner_obj in [first, rest] {
+                        if unsafe { GlobalImmixAllocator::mark(inner_obj, &mut live_bytes) } {
+                            stack.push(inner_obj);
+                        }
+                    }
+                }
+                if stack.len() >= MARK_SHARE_MIN {
+                    self.share(id, &mut stack);
+                }
+            }
+            if self.steal(id, &mut stack) {
+                continue;
+            }
+
+            // a thread only goes idle once it has found every shared stack
+            // empty, so when the last one does nothing is left to trace
+            let mut state = self.state.lock().unwrap();
+            state.active -= 1;
+            loop {
+                if state.active == 0 {
+                    self.wake.notify_all();
+                    return live_bytes;
+                }
+                // counted before looking, so that a thread sharing after the
+                // stacks were found empty knows to wake this one
+                self.waiting.fetch_add(1, Ordering::SeqCst);
+                if self.steal(id, &mut stack) {
+                    self.waiting.fetch_sub(1, Ordering::SeqCst);
+                    state.active += 1;
+                    break;
+                }
+                state = self.wake.wait(state).unwrap();
+                self.waiting.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Moves the bottom half of `stack` to the shared stack of thread `id`,
+    /// unless the other threads have yet to take what it shared last, and
+    /// wakes the idle threads to steal it
+    fn share(&self, id: usize, stack: &mut Vec<PackedPtr>) {
+        if let Ok(mut shared) = self.shared[id].try_lock() {
+            if shared.is_empty() {
+                shared.extend(stack.drain(..stack.len() / 2));
+                drop(shared);
+                if self.waiting.load(Ordering::SeqCst) > 0 {
+                    // idle threads look and wait under the lock, so none can
+                    // miss this between the two
+                    drop(self.state.lock().unwrap());
+                    self.wake.notify_all();
+                }
+            }
+        }
+    }
+
+    /// Takes back what thread `id` shared, or else the top half of another
+    /// thread's shared stack. Returns whether it found anything.
+    fn steal(&self, id: usize, stack: &mut Vec<PackedPtr>) -> bool {
+        let threads = self.shared.len();
+        for victim in (0..threads).map(|i| (id + i) % threads) {
+            let mut shared = self.shared[victim].lock().unwrap();
+            if !shared.is_empty() {
+                let keep = if victim == id { 0 } else { shared.len() / 2 };
+                stack.extend(shared.drain(keep..));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Copies the conses out of fragmented blocks during a collection into fresh
+/// blocks, leaving forwarding pointers so every later reference finds the copy
+struct Evacuator {
+    candidates: SortedVec<Block>,
+    target: Option<ImmixBlockHandler>,
+    targets: Vec<Block>,
+    // empty blocks of the heap, copied into before any new block is allocated
+    free: Vec<Block>,
+    max_new_blocks: usize,
+    new_blocks: usize,
+}
+
+impl Evacuator {
+    fn new(candidates: Vec<Block>, free: Vec<Block>, max_new_blocks: usize) -> Self {
+        Evacuator {
+            candidates: SortedVec::from_vec(candidates),
+            target: None,
+            targets: vec![],
+            free,
+            max_new_blocks,
+            new_blocks: 0,
+        }
+    }
+
+    fn cons_in_candidate(&self, obj: PackedPtr) -> Option<NonNull<RawCons>> {
+        let ptr = match obj.unpack() {
+            UnpackedPtr::Cons(ptr) | UnpackedPtr::Object(ptr) => ptr,
+            _ => return None,
+        };
+        let (block, _, _) = unsafe { Block::block_from_ptr(ptr.as_ptr() as *mut u8) };
+        self.candidates.binary_search(&block).ok().map(|_| ptr)
+    }
+
+    fn retag(obj: PackedPtr, ptr: NonNull<RawCons>) -> PackedPtr {
+        match obj.unpack() {
+            UnpackedPtr::Object(_) => PackedPtr::obj_ptr(ptr),
+            _ => PackedPtr::cons_ptr(ptr),
+        }
+    }
+
+    /// Where `obj` lives once evacuated, copying it now if it has not been yet.
+    /// Conses stay put when no block can be had to copy them into.
+    unsafe fn evacuate(&mut self, obj: PackedPtr) -> PackedPtr {
+        let Some(from) = self.cons_in_candidate(obj) else {
+            return obj;
+        };
+        if let Some(to) = unsafe { from.as_ref() }.forwarded() {
+            return Self::retag(obj, to);
+        }
+        let Some(to) = self.alloc() else {
+            return obj;
+        };
+        unsafe {
+            to.as_ptr().write(*from.as_ptr());
+            (*from.as_ptr()).forward(to);
+        }
+        Self::retag(obj, to)
+    }
+
+    /// Evacuates the objects `obj` refers to and points it at their copies
+    unsafe fn evacuate_fields(&mut self, obj: PackedPtr) {
+        if let UnpackedPtr::Cons(ptr) | UnpackedPtr::Object(ptr) = obj.unpack() {
+            let cons = unsafe { &mut *ptr.as_ptr() };
+            cons.first = unsafe { self.evacuate(cons.first) };
+            cons.rest = unsafe { self.evacuate(cons.rest) };
+        }
+    }
+
+    /// Where `obj` was moved to, once the collection is done
+    unsafe fn forwarded(&self, obj: PackedPtr) -> PackedPtr {
+        match self
+            .cons_in_candidate(obj)
+            .and_then(|from| unsafe { from.as_ref() }.forwarded())
+        {
+            Some(to) => Self::retag(obj, to),
+            None => obj,
+        }
+    }
+
+    fn alloc(&mut self) -> Option<NonNull<RawCons>> {
+        let size = size_of::<RawCons>();
+        if let Some(ptr) = self.target.as_mut().and_then(|t| t.bump.bump(size)) {
+            return Some(ptr);
+        }
+        let block = match self.free.pop() {
+            Some(block) => block,
+            None if self.new_blocks < self.max_new_blocks => {
+                let block = Block::new().ok()?;
+                self.new_blocks += 1;
+                block
+            }
+            None => return None,
+        };
+        let mut target = unsafe { ImmixBlockHandler::from_block_and_lines(block, 0, IMMIX_LINES) };
+        let ptr = target.bump.bump(size);
+        self.targets.push(target.block.clone());
+        self.target = Some(target);
+        ptr
+    }
+}
+
+struct ImmixMutatorState {
+    head: ImmixBlockHandler,
+    blocks: SortedVec<Block>,
+    roots: RootList,
+    start_recycle: bool,
+    // bytes allocated since the last collection
+    allocated: usize,
+    // marks objects as they are allocated, for the heap verifier
+    verify: bool,
+    // set while marking is under way, which also marks objects as they are
+    // allocated, and keeps the values the write barrier sees overwritten
+    marking: bool,
+    satb: Vec<PackedPtr>,
+    // collect on every this many allocations, if set
+    stress_interval: Option<usize>,
+    allocations_until_stress_gc: usize,
+    // old objects written since the last collection; see `ImmixMutator::write_barrier`
+    remembered: Vec<PackedPtr>,
+}
+
+// the roots live on the stack of the mutator's thread; other threads only walk
+// them while collecting, when that thread is parked at a safepoint
+unsafe impl Send for ImmixMutatorState {}
+
+impl ImmixMutatorState {
+    // counts an allocation, returning whether stress mode collects before it
+    fn stress_gc_due(&mut self) -> bool {
+        let Some(interval) = self.stress_interval else {
+            return false;
+        };
+        self.allocations_until_stress_gc -= 1;
+        if self.allocations_until_stress_gc == 0 {
+            self.allocations_until_stress_gc = interval;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn find_hole<'a, I: Iterator<Item = &'a Block>>(
+        iter: I,
+        size: usize,
+    ) -> Option<ImmixBlockHandler> {
+        for b in iter {
+            if let Some(bh) = ImmixBlockHandler::from_block(b.clone(), size, 0) {
+                return Some(bh);
+            }
+        }
+        None
     }
 
     fn try_recycle<T>(&mut self, size: usize) -> Option<NonNull<T>> {
@@ -466,7 +1797,10 @@ impl ImmixMutatorState {
         }
     }
 
-    fn set_head(&mut self, bh: ImmixBlockHandler) {
+    // The lines a mutator bumps through are marked as soon as it gets them, or
+    // a later search for holes would hand out the objects it allocated there
+    fn set_head(&mut self, mut bh: ImmixBlockHandler) {
+        bh.mark_bump_range();
         self.head = bh;
     }
 
@@ -495,42 +1829,266 @@ impl ImmixMutatorState {
     }
 }
 
+/// Allocates on behalf of the thread that created it. Collections it triggers
+/// first stop the mutators of every other thread at a safepoint.
 pub struct ImmixMutator<'a> {
     global: &'a Mutex<GlobalImmixAllocator>,
     local_state: Arc<Mutex<ImmixMutatorState>>,
+    safepoint: Arc<Safepoint>,
+    source_map: Arc<Mutex<SourceMap>>,
+    // registered with the safepoint by thread, so it must stay on its thread
+    _thread: PhantomData<*const ()>,
 }
 
 impl<'a> ImmixMutator<'a> {
-    pub fn new(global: &'a Mutex<GlobalImmixAllocator>) -> Self {
+    /// Registers a mutator for the calling thread, failing with `OutOfMemory`
+    /// if there is no room left in the heap for its first block
+    pub fn new(global: &'a Mutex<GlobalImmixAllocator>) -> Result<Self, AllocError> {
+        // registering waits out any collection, which needs the global lock
+        let safepoint = global.lock().unwrap().safepoint.clone();
+        safepoint.register();
+
         let mut lock = global.lock().unwrap();
-        let head = lock.request_block(IMMIX_MIN_STARTING_SIZE, false).unwrap();
+        let mut head = match lock.request_block(IMMIX_MIN_STARTING_SIZE, false) {
+            Ok(head) => head,
+            Err(err) => {
+                drop(lock);
+                safepoint.unregister();
+                return Err(err);
+            }
+        };
+        head.mark_bump_range();
         let block = head.block.clone();
         let local_state = Arc::new(Mutex::new(ImmixMutatorState {
             head,
             blocks: unsafe { SortedVec::from_sorted_vec(vec![block]) },
             roots: RootList::new(),
             start_recycle: false,
+            allocated: 0,
+            verify: lock.verify,
+            marking: lock.cycle.is_some(),
+            satb: Vec::new(),
+            stress_interval: None,
+            allocations_until_stress_gc: 0,
+            remembered: Vec::new(),
         }));
         lock.add_local_list(local_state.clone());
-        ImmixMutator {
+        let source_map = lock.source_map.clone();
+        drop(lock);
+        let mutator = ImmixMutator {
             global,
             local_state,
+            safepoint,
+            source_map,
+            _thread: PhantomData,
+        };
+        if let Some(interval) = std::env::var_os(STRESS_GC_ENV_VAR) {
+            let interval = interval
+                .to_str()
+                .and_then(|n| n.parse().ok())
+                .expect("LISP_RS_STRESS_GC takes a number of allocations");
+            mutator.set_stress_gc(Some(interval));
+        }
+        Ok(mutator)
+    }
+
+    /// Collects before every `interval`th allocation this mutator makes, or
+    /// every allocation for 1, so that a builtin holding an unrooted pointer
+    /// across an allocation sees it freed. Turning it on also has collections
+    /// poison what they free. Off unless the `LISP_RS_STRESS_GC` environment
+    /// variable is set.
+    pub fn set_stress_gc(&self, interval: Option<usize>) {
+        assert!(interval != Some(0), "stress GC interval must be positive");
+        if interval.is_some() {
+            self.global.lock().unwrap().set_poison(true);
+        }
+        let mut list = self.local_state.lock().unwrap();
+        list.stress_interval = interval;
+        list.allocations_until_stress_gc = interval.unwrap_or(0);
+    }
+
+    /// The interval `set_stress_gc` last set, if any
+    pub fn stress_gc(&self) -> Option<usize> {
+        self.local_state.lock().unwrap().stress_interval
+    }
+
+    /// Must be called whenever a field of `obj` is about to be changed from
+    /// `old` to `value` after `obj` was allocated. While marking is under
+    /// way, `old` is kept for it to trace, since it may be the last path to
+    /// objects reachable when marking started, all of which it has to mark.
+    /// And an old object left pointing at a young one is remembered, so that
+    /// the next nursery collection, which does not trace old objects, still
+    /// finds the young one.
+    pub fn write_barrier(&self, obj: PackedPtr, old: PackedPtr, value: PackedPtr) {
+        let mut list = self.local_state.lock().unwrap();
+        unsafe {
+            // symbols count as marked, but they are marked in their arena
+            let symbol = matches!(old.unpack(), UnpackedPtr::Symbol(_));
+            if list.marking && (symbol || !GlobalImmixAllocator::marked(old)) {
+                list.satb.push(old);
+            }
+            if GlobalImmixAllocator::marked(obj)
+                && !GlobalImmixAllocator::marked(value)
+                && list.remembered.last() != Some(&obj)
+            {
+                list.remembered.push(obj);
+            }
         }
     }
 
     pub fn add_root(&self, root: Pin<&RootNode>) {
         self.local_state.lock().unwrap().roots.add_root(root);
     }
+
+    pub fn record_source(&self, ptr: PackedPtr, pos: SourcePos) {
+        self.source_map.lock().unwrap().insert(ptr, pos);
+    }
+
+    /// Parks this thread if another is waiting to collect; see
+    /// `Safepoint::poll` for what stays valid across it. Allocating polls
+    /// too, so this is only needed on paths that may run long without
+    /// allocating.
+    #[inline]
+    pub fn safepoint(&self) {
+        self.safepoint.poll()
+    }
+
+    /// Runs `f` without holding up collections on other threads, for waits
+    /// that may block; see `Safepoint::park_while`
+    pub fn park_while<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        self.safepoint.park_while(f)
+    }
+
+    /// See `GlobalImmixAllocator::defragment`. Returns false without moving
+    /// anything if another thread collected meanwhile. While other threads
+    /// have mutators it collects without moving anything instead, since
+    /// they may be parked with unrooted pointers into the heap.
+    pub unsafe fn defragment(&self) -> bool {
+        let Some(world) = self.safepoint.stop_the_world() else {
+            return false;
+        };
+        let mut global = self.global.lock().unwrap();
+        if !world.sole_thread() {
+            global.gc();
+            return false;
+        }
+        unsafe { global.defragment() }
+    }
+
+    /// See `GlobalImmixAllocator::fragmented`
+    pub fn fragmented(&self) -> bool {
+        self.global.lock().unwrap().fragmented()
+    }
+
+    /// Collects once the other threads are stopped, unless another thread was
+    /// already collecting, in which case its collection stands in for this one
+    pub fn gc(&self) {
+        if let Some(_world) = self.safepoint.stop_the_world() {
+            self.global.lock().unwrap().gc()
+        }
+    }
+
+    /// Collects to make room for an allocation, returning whether it could
+    /// have freed anything. When collections are incremental it only starts
+    /// one, unless one is under way already, which it finishes.
+    fn collect_for_allocation(&self) -> bool {
+        let Some(_world) = self.safepoint.stop_the_world() else {
+            return true;
+        };
+        let mut global = self.global.lock().unwrap();
+        if global.increment_budget.is_none() || global.verify {
+            global.gc();
+            true
+        } else if global.cycle.is_some() {
+            global.finish_incremental();
+            true
+        } else {
+            global.start_incremental();
+            false
+        }
+    }
+
+    // marks for a while, as its allocations pay for
+    fn step_incremental(&self) {
+        if let Some(_world) = self.safepoint.stop_the_world() {
+            self.global.lock().unwrap().step_incremental();
+        }
+    }
+
+    /// Like `gc`, but always traces the whole heap, even if another thread's
+    /// collection stood in for it
+    pub fn full_gc(&self) {
+        loop {
+            if let Some(_world) = self.safepoint.stop_the_world() {
+                return self.global.lock().unwrap().full_gc();
+            }
+        }
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.global.lock().unwrap().stats()
+    }
+
+    pub fn source_pos(&self, ptr: PackedPtr) -> Option<SourcePos> {
+        self.source_map.lock().unwrap().get(ptr).cloned()
+    }
 }
 
 impl<'a> LAlloc for ImmixMutator<'a> {
-    fn alloc<T, R, F: FnOnce(NonNull<T>) -> R>(&self, transformer: F) -> Result<R, AllocError> {
-        let size = ((size_of::<T>() + OBJECT_ALIGNMENT - 1) / OBJECT_ALIGNMENT) * OBJECT_ALIGNMENT;
+    fn alloc_sized<T, R, F: FnOnce(NonNull<T>) -> R>(
+        &self,
+        size: usize,
+        transformer: F,
+    ) -> Result<R, AllocError> {
+        let size = size.next_multiple_of(OBJECT_ALIGNMENT);
+        self.safepoint.poll();
+        let mut list = self.local_state.lock().unwrap();
+        if list.stress_gc_due() {
+            drop(list);
+            self.gc();
+            list = self.local_state.lock().unwrap();
+        }
+
         if size > IMMIX_USABLE_SIZE {
-            return Err(AllocError::InvalidInput);
+            drop(list);
+            let wants_collection = {
+                let global = self.global.lock().unwrap();
+                global.large_objects.wants_collection() || !global.has_room(size)
+            };
+            if wants_collection {
+                self.collect_for_allocation();
+            }
+            let mut global = self.global.lock().unwrap();
+            if !global.has_room(size) {
+                // garbage that survived earlier collections may be in the way
+                drop(global);
+                self.full_gc();
+                global = self.global.lock().unwrap();
+                if !global.has_room(size) {
+                    return Err(AllocError::OutOfMemory);
+                }
+            }
+            let marked = global.cycle.is_some();
+            return global.large_objects.alloc(size, marked).map(transformer);
         }
 
-        let mut list = self.local_state.lock().unwrap();
+        if list.marking && list.head.bump.free_size() < size {
+            // every hole a mutator fills pays for an increment of marking
+            drop(list);
+            self.step_incremental();
+            list = self.local_state.lock().unwrap();
+        }
+
+        list.allocated += size;
+
+        // objects are marked as they are allocated while marking is under way,
+        // so that it keeps them, or while verifying
+        let transformer = |list: &ImmixMutatorState, ptr: NonNull<T>| {
+            if list.verify || list.marking {
+                unsafe { GlobalImmixAllocator::mark_new(ptr.as_ptr() as *mut u8, size) };
+            }
+            transformer(ptr)
+        };
 
         if list.head.bump.free_size() >= size {
             // let (block, line, offset) =
@@ -538,26 +2096,38 @@ impl<'a> LAlloc for ImmixMutator<'a> {
             // if offset == 0 || offset + size >= IMMIX_LINE_SIZE {
             // println!("BUMP: {block:?} {line} {offset} {:?}", list.blocks.base());
             // }
-            unsafe { Ok(transformer(list.head.bump.unchecked_bump(size))) }
+            let ptr = unsafe { list.head.bump.unchecked_bump(size) };
+            Ok(transformer(&list, ptr))
         } else if let Some(ptr) = list.try_allocate_local(size) {
             // println!("SKIP: 0x{:x} {:?}", ptr.as_ptr() as usize, list.blocks.base());
-            Ok(transformer(ptr))
+            Ok(transformer(&list, ptr))
         } else {
             drop(list);
-            let mut global = self.global.lock().unwrap();
-            let mut res = global.request_block(size, true);
+            let mut res = self.global.lock().unwrap().request_block(size, true);
             if let Err(AllocError::GcTryAgain) = res {
-                if let Some(ptr) = self.local_state.lock().unwrap().try_allocate_local(size) {
-                    return Ok(transformer(ptr));
+                if self.collect_for_allocation() {
+                    let mut list = self.local_state.lock().unwrap();
+                    if let Some(ptr) = list.try_allocate_local(size) {
+                        return Ok(transformer(&list, ptr));
+                    }
                 }
-                res = global.request_block(size, false);
+                res = self.global.lock().unwrap().request_block(size, false);
+            }
+            if let Err(AllocError::OutOfMemory) = res {
+                // garbage that survived earlier collections may be in the way
+                self.full_gc();
+                let mut list = self.local_state.lock().unwrap();
+                if let Some(ptr) = list.try_allocate_local(size) {
+                    return Ok(transformer(&list, ptr));
+                }
+                drop(list);
+                res = self.global.lock().unwrap().request_block(size, false);
             }
             if let Ok(block_handler) = res {
                 let mut list = self.local_state.lock().unwrap();
                 list.blocks.insert(block_handler.block.clone());
-                Ok(transformer(unsafe {
-                    list.alloc_head_or_mark(size, block_handler)
-                }))
+                let ptr = unsafe { list.alloc_head_or_mark(size, block_handler) };
+                Ok(transformer(&list, ptr))
             } else {
                 Err(res.unwrap_err())
             }
@@ -567,18 +2137,30 @@ impl<'a> LAlloc for ImmixMutator<'a> {
 
 impl<'a> Drop for ImmixMutator<'a> {
     fn drop(&mut self) {
-        let mut global = self.global.lock().unwrap();
-        let mut list = self.local_state.lock().unwrap();
-        global.remove_local_list(&self.local_state);
-        global.return_blocks(take(unsafe { &mut list.blocks.base_mut() }));
+        self.safepoint.poll();
+        // a panic during a collection, such as a failed verification, leaves
+        // the heap in no state to take the blocks back
+        if let (Ok(mut global), Ok(mut list)) = (self.global.lock(), self.local_state.lock()) {
+            global.remove_local_list(&self.local_state);
+            global.stats.bytes_allocated += list.allocated;
+            global.remembered.append(&mut list.remembered);
+            global.satb.append(&mut list.satb);
+            global.return_blocks(take(unsafe { list.blocks.base_mut() }));
+        }
+        self.safepoint.unregister();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::builtins::unpack::unpack_cons;
     use crate::heap::id;
     use crate::heap::LAlloc;
+    use crate::let_slot;
+    use crate::object::RawString;
+    use crate::value::Value;
+    use std::collections::HashSet;
 
     #[test]
     fn test_block() {
@@ -592,7 +2174,7 @@ mod tests {
     #[test]
     fn test_alloc() {
         let global = Mutex::new(GlobalImmixAllocator::new());
-        let allocator = ImmixMutator::new(&global);
+        let allocator = ImmixMutator::new(&global).unwrap();
 
         for _ in 0..IMMIX_USABLE_SIZE / OBJECT_ALIGNMENT {
             let _: NonNull<u8> = allocator.alloc(id).unwrap();
@@ -610,7 +2192,8 @@ mod tests {
     #[test]
     fn test_round_robin() {
         let state = Mutex::new(GlobalImmixAllocator::new());
-        let allocators: Vec<ImmixMutator> = (0..4).map(|_| ImmixMutator::new(&state)).collect();
+        let allocators: Vec<ImmixMutator> =
+            (0..4).map(|_| ImmixMutator::new(&state).unwrap()).collect();
 
         for i in 0..IMMIX_USABLE_SIZE / OBJECT_ALIGNMENT * 4 {
             let _: NonNull<u8> = allocators[i % 4].alloc(id).unwrap();
@@ -630,4 +2213,635 @@ mod tests {
             .sum();
         assert_eq!(n, 1);
     }
+
+    fn blocks_of(list: PackedPtr) -> usize {
+        let mut blocks = std::collections::BTreeSet::new();
+        let mut rest = list;
+        while let UnpackedPtr::Cons(ptr) = rest.unpack() {
+            blocks.insert(unsafe { Block::block_from_ptr(ptr.as_ptr() as *mut u8) }.0);
+            rest = unsafe { ptr.as_ref() }.rest;
+        }
+        blocks.len()
+    }
+
+    #[test]
+    fn test_threads() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        global.alloc_state.lock().unwrap().set_poison(true);
+        let global: &'static crate::thread::GlobalState = global;
+
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    let ctx = crate::thread::MutatorCtx::new_from_global(global);
+                    let_slot!(ctx: list, ctx: garbage);
+                    let mut list = list.nil();
+                    for i in 0..1000 {
+                        list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+                    }
+                    let mut garbage = garbage.nil();
+
+                    for i in 0..2000 {
+                        ctx.alloc.safepoint();
+                        // between safepoints the list may go unrooted, even
+                        // while other threads want to collect
+                        let raw = unsafe { list.packed() };
+                        list.set_raw(PackedPtr::nil());
+                        let mut sum = 0;
+                        let mut rest = raw;
+                        while let UnpackedPtr::Cons(ptr) = rest.unpack() {
+                            if let UnpackedPtr::Integer(n) = unsafe { ptr.as_ref() }.first.unpack()
+                            {
+                                sum += n;
+                            }
+                            rest = unsafe { ptr.as_ref() }.rest;
+                        }
+                        assert_eq!(sum, 999 * 1000 / 2);
+                        list.set_raw(raw);
+
+                        garbage = garbage.prepend(&ctx, &Value::Nil.pack()).unwrap();
+                        if i % 100 == t {
+                            garbage = garbage.slot().nil();
+                            ctx.alloc.gc();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert!(global.gc_stats().collections >= 40);
+    }
+
+    #[test]
+    fn test_evacuation() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_verify(true);
+
+        // every cons of `list` is followed by four lines of conses that only
+        // die once everything is allocated
+        let_slot!(ctx: list, ctx: garbage);
+        let mut list = list.nil();
+        let mut garbage = garbage.nil();
+        for i in 0..400 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+            for _ in 0..31 {
+                garbage = garbage.prepend(&ctx, &Value::Nil.pack()).unwrap();
+            }
+        }
+        garbage.slot().nil();
+
+        global.alloc_state.lock().unwrap().gc();
+        let before = blocks_of(unsafe { list.packed() });
+        assert!(unsafe { ctx.alloc.defragment() });
+        let after = blocks_of(unsafe { list.packed() });
+        assert!(after < before, "{} blocks before, {} after", before, after);
+
+        let mut rest = list.value();
+        for i in (0..400).rev() {
+            let cons = unpack_cons(rest).unwrap();
+            assert!(cons.first == Value::Integer(i).pack());
+            rest = cons.rest;
+        }
+        assert!(rest == Value::Nil.pack());
+    }
+
+    #[test]
+    fn test_defragment_after_nursery_collection() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        ctx.alloc.set_stress_gc(None);
+        // verifying makes every collection full, leaving no nursery one
+        global.alloc_state.lock().unwrap().set_verify(false);
+
+        let_slot!(ctx: list, ctx: garbage);
+        let mut list = list.nil();
+        let mut garbage = garbage.nil();
+        for i in 0..400 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+            for _ in 0..31 {
+                garbage = garbage.prepend(&ctx, &Value::Nil.pack()).unwrap();
+            }
+        }
+        global.alloc_state.lock().unwrap().full_gc();
+
+        // the garbage only dies once it is old, so the nursery collection
+        // keeps its lines marked
+        garbage.slot().nil();
+        let full_collections = global.gc_stats().full_collections;
+        global.alloc_state.lock().unwrap().gc();
+        assert_eq!(global.gc_stats().full_collections, full_collections);
+
+        let before = blocks_of(unsafe { list.packed() });
+        assert!(unsafe { ctx.alloc.defragment() });
+        let after = blocks_of(unsafe { list.packed() });
+        assert!(after < before, "{} blocks before, {} after", before, after);
+    }
+
+    #[test]
+    fn test_evacuation_heap_limit() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_verify(true);
+
+        // the spare conses fill whole blocks, which are left empty to copy into
+        let_slot!(ctx: list, ctx: garbage, ctx: spare);
+        let mut list = list.nil();
+        let mut garbage = garbage.nil();
+        for i in 0..400 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+            for _ in 0..31 {
+                garbage = garbage.prepend(&ctx, &Value::Nil.pack()).unwrap();
+            }
+        }
+        let mut spare = spare.nil();
+        for _ in 0..8000 {
+            spare = spare.prepend(&ctx, &Value::Nil.pack()).unwrap();
+        }
+        garbage.slot().nil();
+        spare.slot().nil();
+
+        let mut state = global.alloc_state.lock().unwrap();
+        state.gc();
+        let heap_size = state.heap_size();
+        state.set_max_heap_size(Some(heap_size));
+        drop(state);
+
+        let before = blocks_of(unsafe { list.packed() });
+        assert!(unsafe { ctx.alloc.defragment() });
+        let after = blocks_of(unsafe { list.packed() });
+        assert!(after < before, "{} blocks before, {} after", before, after);
+        assert_eq!(global.alloc_state.lock().unwrap().heap_size(), heap_size);
+
+        let mut rest = list.value();
+        for i in (0..400).rev() {
+            let cons = unpack_cons(rest).unwrap();
+            assert!(cons.first == Value::Integer(i).pack());
+            rest = cons.rest;
+        }
+        assert!(rest == Value::Nil.pack());
+    }
+
+    #[test]
+    fn test_defragment_with_other_threads() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_verify(true);
+        let global: &'static crate::thread::GlobalState = global;
+
+        let_slot!(ctx: list, ctx: garbage);
+        let mut list = list.nil();
+        let mut garbage = garbage.nil();
+        for i in 0..400 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+            for _ in 0..31 {
+                garbage = garbage.prepend(&ctx, &Value::Nil.pack()).unwrap();
+            }
+        }
+        garbage.slot().nil();
+        global.alloc_state.lock().unwrap().gc();
+
+        // another thread parks holding an unrooted pointer into the list
+        let head = crate::thread::SendPtr(unsafe { list.packed() });
+        let (ready, wait_ready) = std::sync::mpsc::channel();
+        let (wake, wait_wake) = std::sync::mpsc::channel::<()>();
+        let waiter = std::thread::spawn(move || {
+            let head = head;
+            let ctx = crate::thread::MutatorCtx::new_from_global(global);
+            ready.send(()).unwrap();
+            ctx.alloc.park_while(|| wait_wake.recv().unwrap());
+            match head.0.unpack() {
+                UnpackedPtr::Cons(cons) => {
+                    unsafe { cons.as_ref() }.first
+                        == unsafe { Value::Integer(399).pack().unguard() }
+                }
+                _ => false,
+            }
+        });
+        wait_ready.recv().unwrap();
+
+        let before = blocks_of(unsafe { list.packed() });
+        assert!(!unsafe { ctx.alloc.defragment() });
+        assert_eq!(blocks_of(unsafe { list.packed() }), before);
+        wake.send(()).unwrap();
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "heap verification failed before collection")]
+    fn test_verify_freed_root() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_verify(true);
+
+        let_slot!(ctx: list, ctx: stale);
+        let list = list.nil().prepend(&ctx, &Value::Integer(1).pack()).unwrap();
+        let freed = unsafe { list.packed() };
+        list.slot().nil();
+        global.alloc_state.lock().unwrap().gc();
+
+        // a root the collector could not see when it freed the cons
+        let _stale = stale.root_raw(freed);
+        global.alloc_state.lock().unwrap().gc();
+    }
+
+    #[test]
+    fn test_large_objects() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        let big = "x".repeat(IMMIX_USABLE_SIZE * 2);
+
+        let_slot!(ctx: kept, ctx: dropped);
+        let kept = kept.alloc_string(&ctx, &big).unwrap();
+        dropped.alloc_string(&ctx, &big).unwrap().slot().nil();
+
+        let mut state = global.alloc_state.lock().unwrap();
+        assert_eq!(state.large_objects.objects.len(), 2);
+        state.gc();
+        assert_eq!(state.large_objects.objects.len(), 1);
+        assert_eq!(
+            state.large_objects.live_size,
+            RawString::alloc_size(big.len())
+        );
+        drop(state);
+
+        match unsafe { kept.packed() }.unpack() {
+            UnpackedPtr::String(ptr) => assert_eq!(unsafe { ptr.as_ref() }.as_str(), big),
+            _ => panic!("not a string"),
+        }
+    }
+
+    #[test]
+    fn test_release_free_blocks() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_free_block_budget(4);
+
+        let_slot!(ctx: list);
+        let mut list = list.nil();
+        for i in 0..200_000 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+        let peak = global.alloc_state.lock().unwrap().block_counts();
+        assert!(peak.in_use >= 100, "{:?}", peak);
+
+        // the list survived the collections that made room for it, so only a
+        // full collection frees it
+        list.slot().nil();
+        let mut state = global.alloc_state.lock().unwrap();
+        state.full_gc();
+        let after = state.block_counts();
+        assert_eq!(after.free, 4);
+        assert!(after.in_use <= 2, "{:?}", after);
+    }
+
+    #[test]
+    fn test_nursery_collection() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_poison(true);
+        // the test counts collections, which stress collections would add to,
+        // and nursery ones, which verifying would make full. Turning
+        // verification off collects, so counting starts after it.
+        ctx.alloc.set_stress_gc(None);
+        global.alloc_state.lock().unwrap().set_verify(false);
+        let before = global.gc_stats();
+
+        let_slot!(ctx: old, ctx: young);
+        let mut old = old.nil();
+        for i in 0..200 {
+            old = old.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+        global.alloc_state.lock().unwrap().full_gc();
+        let old_bytes = global.gc_stats().live_bytes;
+
+        let mut young = young.nil();
+        for i in 0..200 {
+            young = young.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+        young = young.slot().nil();
+        for i in 0..10 {
+            young = young.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+        global.alloc_state.lock().unwrap().gc();
+
+        let stats = global.gc_stats();
+        assert_eq!(
+            (
+                stats.collections - before.collections,
+                stats.full_collections - before.full_collections
+            ),
+            (2, 1)
+        );
+        assert_eq!(stats.live_bytes, old_bytes + 10 * size_of::<RawCons>());
+        for (list, len) in [(old.value(), 200), (young.value(), 10)] {
+            let mut rest = list;
+            for i in (0..len).rev() {
+                let cons = unpack_cons(rest).unwrap();
+                assert!(cons.first == Value::Integer(i).pack());
+                rest = cons.rest;
+            }
+            assert!(rest == Value::Nil.pack());
+        }
+    }
+
+    #[test]
+    fn test_write_barrier() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_poison(true);
+        // only a nursery collection relies on the barrier, and verifying
+        // would make it full
+        global.alloc_state.lock().unwrap().set_verify(false);
+
+        let_slot!(ctx: old, ctx: young);
+        let old = old.nil().prepend(&ctx, &Value::Nil.pack()).unwrap();
+        global.alloc_state.lock().unwrap().full_gc();
+        let full_collections = global.gc_stats().full_collections;
+
+        // a young cons only the old one points at, which a nursery collection
+        // does not trace unless the barrier remembered it
+        let young = young
+            .nil()
+            .prepend(&ctx, &Value::Integer(7).pack())
+            .unwrap();
+        let UnpackedPtr::Cons(cons) = unsafe { old.packed() }.unpack() else {
+            panic!("not a cons");
+        };
+        unsafe {
+            ctx.alloc
+                .write_barrier(old.packed(), (*cons.as_ptr()).first, young.packed());
+            (*cons.as_ptr()).first = young.packed();
+        }
+        young.slot().nil();
+        global.alloc_state.lock().unwrap().gc();
+        assert_eq!(global.gc_stats().full_collections, full_collections);
+
+        let cell = unpack_cons(old.value()).unwrap().first;
+        assert!(unsafe { GlobalImmixAllocator::marked(cell.unguard()) });
+        assert!(unpack_cons(cell).unwrap().first == Value::Integer(7).pack());
+    }
+
+    #[test]
+    fn test_incremental_marking() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_poison(true);
+        global.alloc_state.lock().unwrap().set_incremental(Some(16));
+        // stress collections are whole, and would leave no increments to count
+        ctx.alloc.set_stress_gc(None);
+
+        let_slot!(ctx: list, ctx: garbage);
+        let garbage = garbage.nil().prepend(&ctx, &Value::Nil.pack()).unwrap();
+        let freed = unsafe { garbage.packed() };
+        garbage.slot().nil();
+        let mut list = list.nil();
+        for i in 0..200 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+        global.alloc_state.lock().unwrap().start_incremental();
+
+        // the list grows while it is marked, a little at a time
+        let mut len = 200;
+        while global.alloc_state.lock().unwrap().cycle.is_some() {
+            global.alloc_state.lock().unwrap().step_incremental();
+            list = list.prepend(&ctx, &Value::Integer(len).pack()).unwrap();
+            len += 1;
+        }
+
+        let stats = global.gc_stats();
+        assert_eq!(stats.collections, 1);
+        assert!(stats.increments > 200 / 16, "{:?}", stats);
+        assert_eq!(stats.recent_pauses.len(), stats.increments);
+        assert!(!unsafe { GlobalImmixAllocator::marked(freed) });
+
+        let mut rest = list.value();
+        for i in (0..len).rev() {
+            let cons = unpack_cons(rest).unwrap();
+            assert!(cons.first == Value::Integer(i).pack());
+            rest = cons.rest;
+        }
+        assert!(rest == Value::Nil.pack());
+    }
+
+    #[test]
+    fn test_increment_pause() {
+        // counts the increments one incremental collection of a long list
+        // takes, with no limit on objects so that only `pause` can cut it up
+        fn increments(pause: Option<Duration>) -> usize {
+            let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+            let ctx = crate::thread::MutatorCtx::new_from_global(global);
+            ctx.alloc.set_stress_gc(None);
+            global.alloc_state.lock().unwrap().set_verify(false);
+
+            let_slot!(ctx: list);
+            let mut list = list.nil();
+            for i in 0..LEN {
+                list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+            }
+            let mut state = global.alloc_state.lock().unwrap();
+            state.set_incremental(Some(usize::MAX));
+            state.set_increment_pause(pause);
+            // a full collection, so that marking goes through the whole list
+            state.set_full_collection_interval(1);
+            state.start_incremental();
+            while state.cycle.is_some() {
+                state.step_incremental();
+            }
+            drop(state);
+            assert!(list.value() != Value::Nil.pack());
+            global.gc_stats().increments
+        }
+        const LEN: isize = 10_000;
+
+        // the start and one increment that marks everything and sweeps
+        assert_eq!(increments(None), 2);
+        // a pause that is over as soon as it starts stops every increment at
+        // the first check of the clock
+        let cut = increments(Some(Duration::ZERO));
+        assert!(cut > LEN as usize / PAUSE_CHECK_INTERVAL, "{}", cut);
+    }
+
+    #[test]
+    fn test_snapshot_barrier() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_poison(true);
+
+        let_slot!(ctx: holder, ctx: inner, ctx: moved);
+        let inner = inner
+            .nil()
+            .prepend(&ctx, &Value::Integer(7).pack())
+            .unwrap();
+        let holder = holder.nil().prepend(&ctx, &inner.value()).unwrap();
+        inner.slot().nil();
+        global.alloc_state.lock().unwrap().start_incremental();
+
+        // the only path to the inner list moves from the holder, which marking
+        // has yet to trace, to a root it has already scanned
+        let UnpackedPtr::Cons(cons) = unsafe { holder.packed() }.unpack() else {
+            panic!("not a cons");
+        };
+        let moved = moved.root_raw(unsafe { (*cons.as_ptr()).first });
+        unsafe {
+            ctx.alloc
+                .write_barrier(holder.packed(), (*cons.as_ptr()).first, PackedPtr::nil());
+            (*cons.as_ptr()).first = PackedPtr::nil();
+        }
+        while global.alloc_state.lock().unwrap().cycle.is_some() {
+            global.alloc_state.lock().unwrap().step_incremental();
+        }
+
+        assert!(unsafe { GlobalImmixAllocator::marked(moved.packed()) });
+        assert!(unpack_cons(moved.value()).unwrap().first == Value::Integer(7).pack());
+    }
+
+    // the line and object marks of every block, by the address of the block
+    fn heap_marks(state: &GlobalImmixAllocator) -> std::collections::BTreeMap<usize, Vec<u8>> {
+        let locals = state.local_lists.lock().unwrap();
+        let local_blocks: Vec<Block> = locals
+            .iter()
+            .flat_map(|l| l.lock().unwrap().blocks.base().to_vec())
+            .collect();
+        let blocks = state.blocks.lock().unwrap();
+        blocks
+            .iter()
+            .chain(&local_blocks)
+            .map(|b| {
+                let meta = unsafe { b.ptr.add(IMMIX_USABLE_SIZE) };
+                let marks = unsafe { std::slice::from_raw_parts(meta, IMMIX_META_SIZE) };
+                (b.ptr as usize, marks.to_vec())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_marking() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_poison(true);
+        let big = "x".repeat(IMMIX_USABLE_SIZE * 2);
+
+        // rows of different lengths sharing a tail, so that the mark stacks
+        // grow wide enough to share, with every third row garbage
+        let_slot!(ctx: tail, ctx: rows, ctx: row, ctx: garbage);
+        let mut tail = tail.nil();
+        for i in 0..50 {
+            tail = tail.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+        let mut rows = rows.nil();
+        let mut garbage = garbage.nil();
+        let mut row = row.nil();
+        for i in 0..600 {
+            row = row.slot().root_raw(unsafe { tail.packed() });
+            for j in 0..i % 20 {
+                row = row.prepend(&ctx, &Value::Integer(j).pack()).unwrap();
+            }
+            if i % 3 == 0 {
+                garbage = garbage.prepend(&ctx, &row.value()).unwrap();
+            } else {
+                rows = rows.prepend(&ctx, &row.value()).unwrap();
+            }
+        }
+        row = row.slot().alloc_string(&ctx, &big).unwrap();
+        rows = rows.prepend(&ctx, &row.value()).unwrap();
+        row = row.slot().alloc_string(&ctx, &big).unwrap();
+        garbage = garbage.prepend(&ctx, &row.value()).unwrap();
+        row.slot().nil();
+        garbage.slot().nil();
+
+        let mut state = global.alloc_state.lock().unwrap();
+        state.full_gc();
+        let serial = heap_marks(&state);
+        let serial_stats = state.stats();
+        assert_eq!(state.large_objects.objects.len(), 1);
+
+        // full collections clear every mark first, so the later ones mark the
+        // same heap from scratch, each on the same parked mark threads
+        state.set_mark_threads(4);
+        for _ in 0..3 {
+            state.full_gc();
+            let parallel_stats = state.stats();
+            assert_eq!(parallel_stats.live_bytes, serial_stats.live_bytes);
+            assert_eq!(parallel_stats.blocks, serial_stats.blocks);
+            assert!(heap_marks(&state) == serial);
+        }
+        assert_eq!(state.mark_pool.as_ref().unwrap().workers.len(), 3);
+        drop(state);
+
+        let mut rest = rows.value();
+        let mut len = 0;
+        while let Ok(cons) = unpack_cons(rest) {
+            len += 1;
+            rest = cons.rest;
+        }
+        assert_eq!(len, 401);
+    }
+
+    // The mark loop as it was before mark bits: objects seen go in a HashSet,
+    // and the pointers out of each object are collected into Vecs. Returns
+    // the bytes marked, as `used_space` counted them.
+    fn hash_set_trace(root: PackedPtr) -> usize {
+        let mut stack = vec![root];
+        let mut seen = HashSet::new();
+        seen.insert(root);
+        let mut used_space = 0usize;
+
+        while let Some(obj) = stack.pop() {
+            let heap_ptrs: Vec<(NonNull<u8>, usize)> = obj.heap_ptr().into_iter().collect();
+            for (ptr, size) in heap_ptrs {
+                unsafe { GlobalImmixAllocator::mark_ptr(ptr.as_ptr(), size) };
+                used_space += size;
+            }
+
+            let obj_ptrs: Vec<PackedPtr> = match obj.obj_ptrs() {
+                Some((first, rest)) => vec![first, rest],
+                None => vec![],
+            };
+            for inner_obj in obj_ptrs {
+                if seen.insert(inner_obj) {
+                    stack.push(inner_obj);
+                }
+            }
+        }
+        used_space
+    }
+
+    // cargo test --release bench_mark -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_mark_million_conses() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: list);
+        let mut list = list.nil();
+        for i in 0..1_000_000 {
+            list = list.prepend(&ctx, &Value::Integer(i).pack()).unwrap();
+        }
+
+        // full collections, since a nursery collection stops at the old list
+        let runs = 10;
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            global.alloc_state.lock().unwrap().full_gc();
+        }
+        let mark_bits = start.elapsed() / runs;
+
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            let used_space = hash_set_trace(unsafe { list.packed() });
+            assert_eq!(used_space, 1_000_000 * size_of::<RawCons>());
+        }
+        let hash_set = start.elapsed() / runs;
+
+        println!(
+            "{:?} per collection, {:?} per trace with a HashSet ({:.1}x)",
+            mark_bits,
+            hash_set,
+            hash_set.as_secs_f64() / mark_bits.as_secs_f64()
+        );
+    }
 }
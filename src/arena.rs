@@ -1,40 +1,112 @@
-use std::{collections::HashMap, ptr::NonNull, sync::Mutex};
-
-use crate::{
-    alloc::{GlobalImmixAllocator, ImmixMutator},
-    heap::{id, LAlloc},
-    object::LString,
+use std::{
+    collections::HashMap,
+    ptr::{addr_of_mut, NonNull},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
+use crate::object::LString;
+
+/// An interned symbol. The name comes first, so a pointer to the symbol is
+/// also a pointer to its `LString`.
+#[repr(C)]
+struct Symbol {
+    name: LString,
+    marked: bool,
+    permanent: bool,
+}
+
+/// Interns symbol names. Symbols are only held weakly: after each collection
+/// `sweep` frees the ones it did not mark, along with their names, unless they
+/// were interned as permanent.
 pub struct Arena {
-    map: HashMap<String, NonNull<LString>>,
-    arena: ImmixMutator<'static>,
+    // the keys own the bytes the symbols' names point to
+    map: HashMap<Box<str>, NonNull<Symbol>>,
 }
 
+// the symbols are owned by the arena and only touched under its lock or while
+// the world is stopped for a collection
+unsafe impl Send for Arena {}
+
 impl Arena {
     pub fn new() -> Self {
-        let map = HashMap::new();
-        let state = Box::new(Mutex::new(GlobalImmixAllocator::new()));
-        let arena = ImmixMutator::new(Box::leak(state));
-        Arena { map, arena }
+        Arena {
+            map: HashMap::new(),
+        }
     }
 
-    pub fn get(&mut self, name: &String) -> Option<NonNull<LString>> {
-        self.map.get(name).copied()
+    #[cfg(test)]
+    pub fn get(&self, name: &str) -> Option<NonNull<LString>> {
+        self.map.get(name).map(|sym| sym.cast())
     }
 
     pub fn intern(&mut self, name: String) -> NonNull<LString> {
-        if let Some(sym) = self.map.get(&name) {
-            *sym
-        } else {
-            let sym = self.dangling(name.clone());
-            self.map.insert(name, sym);
-            sym
-        }
+        let sym = match self.map.get(name.as_str()) {
+            Some(sym) => *sym,
+            None => {
+                let name = name.into_boxed_str();
+                let sym = NonNull::from(Box::leak(Box::new(Symbol {
+                    name: LString {
+                        start: name.as_ptr(),
+                        len: name.len(),
+                    },
+                    marked: false,
+                    permanent: false,
+                })));
+                self.map.insert(name, sym);
+                sym
+            }
+        };
+        // the caller has not rooted it yet, so the next sweep must spare it
+        unsafe { (*sym.as_ptr()).marked = true };
+        sym.cast()
+    }
+
+    /// Interns a symbol that is never freed, for references the collector cannot see
+    pub fn intern_permanent(&mut self, name: String) -> NonNull<LString> {
+        let sym = self.intern(name);
+        unsafe { (*sym.cast::<Symbol>().as_ptr()).permanent = true };
+        sym
+    }
+
+    /// Keeps the symbol `name` belongs to alive through the next sweep
+    pub unsafe fn mark(name: NonNull<LString>) {
+        // atomic, as the threads of a parallel mark may meet the same symbol
+        let symbol = name.cast::<Symbol>().as_ptr();
+        let marked = unsafe { AtomicBool::from_ptr(addr_of_mut!((*symbol).marked)) };
+        marked.store(true, Ordering::Relaxed);
+    }
+
+    /// Frees the symbols not marked since the last sweep and clears the marks
+    /// of the others. Returns how many were freed.
+    pub fn sweep(&mut self) -> usize {
+        let before = self.map.len();
+        self.map.retain(|_, sym| unsafe {
+            let sym = sym.as_ptr();
+            if (*sym).marked || (*sym).permanent {
+                (*sym).marked = false;
+                true
+            } else {
+                drop(Box::from_raw(sym));
+                false
+            }
+        });
+        before - self.map.len()
     }
 
-    pub fn dangling(&mut self, name: String) -> NonNull<LString> {
-        unsafe { self.arena.object(id, LString::leak_str(name)).unwrap() }
+    pub fn symbols(&self) -> impl Iterator<Item = NonNull<LString>> + '_ {
+        self.map.values().map(|sym| sym.cast())
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for (_, sym) in self.map.drain() {
+            drop(unsafe { Box::from_raw(sym.as_ptr()) });
+        }
     }
 }
 
@@ -44,12 +116,15 @@ macro_rules! symbol_cache {
             $(pub $sym_name: crate::value::PackedValue<'static>,)*
         }
 
+        // the symbols are permanent and never written, so any thread may read them
+        unsafe impl Sync for $name {}
+
         impl $name {
             pub fn new(arena: &mut Arena) -> Self {
                 $(
                     let value = crate::util::rust_to_lisp_symbol(stringify!($sym_name));
                     $(let value = $sym_str;)?
-                    let $sym_name = crate::value::Value::Symbol(unsafe { crate::root::Gc::new(arena.intern(value.into()).as_ref())}).pack();
+                    let $sym_name = crate::value::Value::Symbol(unsafe { crate::root::Gc::new(arena.intern_permanent(value.into()).as_ref())}).pack();
                 )*
                 Self {
                     $($sym_name,)*
@@ -59,4 +134,30 @@ macro_rules! symbol_cache {
     };
 }
 
-symbol_cache!(CommonSymbols [quote, quasiquote, unquote, t, lambda, _macro: "macro", fexpr, closure]);
+symbol_cache!(CommonSymbols [quote, quasiquote, unquote, t, lambda, _macro: "macro", fexpr, closure, thread, channel, mutex, _if: "if", cond, and, or, when, unless]);
+
+#[cfg(test)]
+mod tests {
+    use crate::let_slot;
+
+    #[test]
+    fn test_unreachable_symbols_freed() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: kept, ctx: dropped);
+        let _kept = kept.intern(&ctx, "kept".into());
+        dropped.intern(&ctx, "dropped".into()).slot().nil();
+
+        // interning marks a symbol, so it outlives the first sweep either way.
+        // Only full collections sweep.
+        for _ in 0..2 {
+            global.alloc_state.lock().unwrap().full_gc();
+        }
+
+        let arena = global.string_arena.lock().unwrap();
+        assert!(arena.get("kept").is_some());
+        assert!(arena.get("dropped").is_none());
+        assert!(arena.get("quote").is_some());
+    }
+}
@@ -0,0 +1,338 @@
+use std::{cmp::Ordering, convert::TryFrom, fmt::Display};
+
+/// Sign-magnitude integer used to compute on bigints outside of the heap.
+/// Limbs are little-endian and never have trailing zeros, so zero is empty.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative = negative && !magnitude.is_empty();
+        BigInt {
+            negative,
+            magnitude,
+        }
+    }
+
+    pub fn from_i128(n: i128) -> Self {
+        let mut rest = n.unsigned_abs();
+        let mut magnitude = vec![];
+        while rest != 0 {
+            magnitude.push(rest as u32);
+            rest >>= 32;
+        }
+        BigInt {
+            negative: n < 0,
+            magnitude,
+        }
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.magnitude.len() > 4 {
+            return None;
+        }
+        let abs = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0u128, |acc, &limb| acc << 32 | limb as u128);
+        if self.negative {
+            0i128.checked_sub_unsigned(abs)
+        } else {
+            i128::try_from(abs).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let abs = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0f64, |acc, &limb| acc * 4294967296.0 + limb as f64);
+        if self.negative {
+            -abs
+        } else {
+            abs
+        }
+    }
+
+    /// `self / other` as a float, even when both are too large for an f64.
+    /// The dividend is shifted up by whole limbs until the quotient has more
+    /// bits than an f64 holds, and the shift is taken back out of the float.
+    /// `other` must not be zero.
+    pub fn div_to_f64(&self, other: &Self) -> f64 {
+        let limbs = (other.magnitude.len() + 3).saturating_sub(self.magnitude.len());
+        let mut shifted = vec![0; limbs];
+        shifted.extend_from_slice(&self.magnitude);
+        let (quotient, _) = div_rem_mag(&shifted, &other.magnitude);
+        let quotient = Self::from_parts(self.negative != other.negative, quotient);
+        scale(quotient.to_f64(), -(limbs as i64) * 32)
+    }
+
+    pub fn negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn magnitude(&self) -> &[u32] {
+        &self.magnitude
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::from_parts(!self.negative, self.magnitude.clone())
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::from_parts(false, self.magnitude.clone())
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::from_parts(self.negative, add_mag(&self.magnitude, &other.magnitude));
+        }
+        match cmp_mag(&self.magnitude, &other.magnitude) {
+            Ordering::Less => {
+                Self::from_parts(other.negative, sub_mag(&other.magnitude, &self.magnitude))
+            }
+            _ => Self::from_parts(self.negative, sub_mag(&self.magnitude, &other.magnitude)),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::from_parts(
+            self.negative != other.negative,
+            mul_mag(&self.magnitude, &other.magnitude),
+        )
+    }
+
+    /// Truncating division; the remainder takes the sign of the dividend.
+    /// Returns `None` when dividing by zero.
+    pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_mag(&self.magnitude, &other.magnitude);
+        Some((
+            Self::from_parts(self.negative != other.negative, quotient),
+            Self::from_parts(self.negative, remainder),
+        ))
+    }
+
+    /// Parses unsigned digits in `radix`; the grammar has already validated them
+    pub fn parse(negative: bool, digits: &str, radix: u32) -> Self {
+        let mut magnitude = vec![];
+        for c in digits.chars() {
+            let mut carry = c.to_digit(radix).unwrap() as u64;
+            for limb in magnitude.iter_mut() {
+                let n = *limb as u64 * radix as u64 + carry;
+                *limb = n as u32;
+                carry = n >> 32;
+            }
+            if carry != 0 {
+                magnitude.push(carry as u32);
+            }
+        }
+        Self::from_parts(negative, magnitude)
+    }
+
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".into();
+        }
+        let mut digits = vec![];
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let mut remainder = 0u64;
+            for limb in magnitude.iter_mut().rev() {
+                let n = remainder << 32 | *limb as u64;
+                *limb = (n / radix as u64) as u32;
+                remainder = n % radix as u64;
+            }
+            while magnitude.last() == Some(&0) {
+                magnitude.pop();
+            }
+            digits.push(std::char::from_digit(remainder as u32, radix).unwrap());
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_mag(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_string_radix(10))
+    }
+}
+
+/// `x * 2^exp`, in steps small enough for `powi` not to overflow on its own
+fn scale(mut x: f64, mut exp: i64) -> f64 {
+    while exp != 0 && x != 0.0 && x.is_finite() {
+        let step = exp.clamp(-1000, 1000);
+        x *= 2f64.powi(step as i32);
+        exp -= step;
+    }
+    x
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let n = limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(n as u32);
+        carry = n >> 32;
+    }
+    if carry != 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+/// Requires `a >= b`
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut n = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = if n < 0 { 1 } else { 0 };
+        if n < 0 {
+            n += 1 << 32;
+        }
+        out.push(n as u32);
+    }
+    out
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let n = out[i + j] as u64 + x as u64 * y as u64 + carry;
+            out[i + j] = n as u32;
+            carry = n >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    out
+}
+
+// Shift-and-subtract long division, one bit of the dividend at a time
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = vec![];
+    for i in (0..a.len() * 32).rev() {
+        let mut carry = (a[i / 32] >> (i % 32)) & 1;
+        for limb in remainder.iter_mut() {
+            let next = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = next;
+        }
+        if carry != 0 {
+            remainder.push(carry);
+        }
+        if cmp_mag(&remainder, b) != Ordering::Less {
+            remainder = sub_mag(&remainder, b);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(str: &str) -> BigInt {
+        match str.strip_prefix('-') {
+            Some(digits) => BigInt::parse(true, digits, 10),
+            None => BigInt::parse(false, str, 10),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_radix() {
+        let n = big("-123456789012345678901234567890");
+        assert_eq!(n.to_string(), "-123456789012345678901234567890");
+        let hex = n.to_string_radix(16);
+        assert_eq!(BigInt::parse(true, &hex[1..], 16), n);
+        assert_eq!(big("0").to_string(), "0");
+        assert_eq!(big("-0"), big("0"));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("340282366920938463463374607431768211456");
+        let b = big("-18446744073709551617");
+        assert_eq!(
+            a.add(&b).to_string(),
+            "340282366920938463444927863358058659839"
+        );
+        assert_eq!(
+            b.sub(&a).to_string(),
+            "-340282366920938463481821351505477763073"
+        );
+        assert_eq!(
+            a.mul(&b).to_string(),
+            "-6277101735386680764176071790128604879565730051895802724352"
+        );
+        let (q, r) = a.div_rem(&b).unwrap();
+        assert_eq!(q.to_string(), "-18446744073709551615");
+        assert_eq!(r.to_string(), "1");
+        assert_eq!(b.div_rem(&big("0")), None);
+    }
+
+    #[test]
+    fn test_i128_conversion() {
+        assert_eq!(BigInt::from_i128(i128::MIN).to_i128(), Some(i128::MIN));
+        assert_eq!(BigInt::from_i128(-5).to_string(), "-5");
+        assert_eq!(
+            big("170141183460469231731687303715884105728").to_i128(),
+            None
+        );
+        assert!(big("-2") < big("1"));
+        assert!(big("-20000000000000000000") < big("-2"));
+    }
+}
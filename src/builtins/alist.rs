@@ -42,8 +42,8 @@ def_builtin!(zip_alist(ctx, out) [keys, vals, init] {
             let key = key.unwrap();
             let val = val.unwrap();
             let_slot!(ctx:entry);
-            let entry = entry.alloc_cons(ctx, Cons { first: key.first, rest: val.first });
-            out = out.prepend(ctx, &entry.value());
+            let entry = entry.alloc_cons(ctx, Cons { first: key.first, rest: val.first })?;
+            out = out.prepend(ctx, &entry.value())?;
             keys_iter = key.rest;
             vals_iter = val.rest;
         }
@@ -0,0 +1,280 @@
+use std::cmp::Ordering;
+
+use crate::{
+    def_builtin,
+    number::{Number, Promoted},
+    root::Slot,
+    thread::MutatorCtx,
+    value::PackedValue,
+};
+
+use super::{
+    unpack::{unpack_cons, unpack_number},
+    BuiltinError, BuiltinResult,
+};
+
+def_builtin!(add(ctx, out) [&rest args] {
+    let sum = reduce("+", Number::Integer(0), args, |a, b| Ok(match a.promote(b) {
+        Promoted::Integers(a, b) => Number::from_i128(a as i128 + b as i128),
+        Promoted::Bigs(a, b) => Number::from_bigint(a.add(&b)),
+        Promoted::Floats(a, b) => Number::Float(a + b),
+    }))?;
+    Ok(out.number(ctx, sum)?)
+});
+
+def_builtin!(sub(ctx, out) [&rest args] {
+    let (first, rest) = split_first("-", args)?;
+    let difference = match unpack_cons(rest) {
+        Ok(_) => reduce("-", first, rest, subtract)?,
+        Err(_) => subtract(Number::Integer(0), first)?,
+    };
+    Ok(out.number(ctx, difference)?)
+});
+
+def_builtin!(mul(ctx, out) [&rest args] {
+    let product = reduce("*", Number::Integer(1), args, |a, b| Ok(match a.promote(b) {
+        Promoted::Integers(a, b) => Number::from_i128(a as i128 * b as i128),
+        Promoted::Bigs(a, b) => Number::from_bigint(a.mul(&b)),
+        Promoted::Floats(a, b) => Number::Float(a * b),
+    }))?;
+    Ok(out.number(ctx, product)?)
+});
+
+// Integer division stays exact when possible and falls back to a float otherwise
+def_builtin!(div(ctx, out) [&rest args] {
+    let (first, rest) = split_first("/", args)?;
+    let quotient = match unpack_cons(rest) {
+        Ok(_) => reduce("/", first, rest, divide)?,
+        Err(_) => divide(Number::Integer(1), first)?,
+    };
+    Ok(out.number(ctx, quotient)?)
+});
+
+// Floored, so the result takes the sign of the divisor
+def_builtin!(modulo(ctx, out) [a|number, b|number] {
+    let remainder = match a.promote(b) {
+        Promoted::Integers(_, 0) => {
+            return Err(BuiltinError::BadArgument("mod: division by zero".into()))
+        }
+        Promoted::Integers(a, b) => match a % b {
+            r if r != 0 && (r < 0) != (b < 0) => Number::Integer(r + b),
+            r => Number::Integer(r),
+        },
+        Promoted::Bigs(a, b) => {
+            let (_, remainder) = a
+                .div_rem(&b)
+                .ok_or_else(|| BuiltinError::BadArgument("mod: division by zero".into()))?;
+            if !remainder.is_zero() && remainder.negative() != b.negative() {
+                Number::from_bigint(remainder.add(&b))
+            } else {
+                Number::from_bigint(remainder)
+            }
+        }
+        Promoted::Floats(a, b) => Number::Float(a - b * (a / b).floor()),
+    };
+    Ok(out.number(ctx, remainder)?)
+});
+
+def_builtin!(abs(ctx, out) [n|number] {
+    let abs = match n {
+        Number::Integer(n) => Number::from_i128((n as i128).abs()),
+        Number::Big(n) => Number::Big(n.abs()),
+        Number::Float(n) => Number::Float(n.abs()),
+    };
+    Ok(out.number(ctx, abs)?)
+});
+
+def_builtin!(min(ctx, out) [&rest args] {
+    Ok(out.root(&select("min", args, Ordering::Less)?))
+});
+
+def_builtin!(max(ctx, out) [&rest args] {
+    Ok(out.root(&select("max", args, Ordering::Greater)?))
+});
+
+def_builtin!(lt(ctx, out) [&rest args] {
+    boolean(ctx, out, chain("<", args, |o| o == Ordering::Less)?)
+});
+
+def_builtin!(le(ctx, out) [&rest args] {
+    boolean(ctx, out, chain("<=", args, |o| o != Ordering::Greater)?)
+});
+
+def_builtin!(num_eq(ctx, out) [&rest args] {
+    boolean(ctx, out, chain("=", args, |o| o == Ordering::Equal)?)
+});
+
+def_builtin!(ge(ctx, out) [&rest args] {
+    boolean(ctx, out, chain(">=", args, |o| o != Ordering::Less)?)
+});
+
+def_builtin!(gt(ctx, out) [&rest args] {
+    boolean(ctx, out, chain(">", args, |o| o == Ordering::Greater)?)
+});
+
+fn boolean<'o>(ctx: &'o MutatorCtx, out: Slot<'o>, b: bool) -> BuiltinResult<'o> {
+    if b {
+        Ok(out.root(&ctx.common_symbols.t))
+    } else {
+        Ok(out.nil())
+    }
+}
+
+/// Checks that every adjacent pair of arguments compares as `test` expects
+fn chain<F: Fn(Ordering) -> bool>(
+    name: &str,
+    args: PackedValue,
+    test: F,
+) -> Result<bool, BuiltinError> {
+    let (mut prev, mut rest) = split_first(name, args)?;
+    let mut holds = true;
+    while let Ok(cons) = unpack_cons(rest) {
+        let next = number_arg(name, cons.first)?;
+        holds &= prev.numeric_cmp(&next).is_some_and(&test);
+        prev = next;
+        rest = cons.rest;
+    }
+    Ok(holds)
+}
+
+/// Returns the argument that compares as `wanted` against all others
+fn select<'a>(
+    name: &str,
+    args: PackedValue<'a>,
+    wanted: Ordering,
+) -> Result<PackedValue<'a>, BuiltinError> {
+    let mut best = unpack_cons(args)
+        .map_err(|_| BuiltinError::NotEnoughArguments {
+            string: name.into(),
+            expected: 1,
+            provided: 0,
+        })?
+        .first;
+    let mut best_number = number_arg(name, best)?;
+    let mut rest = args;
+    while let Ok(cons) = unpack_cons(rest) {
+        let number = number_arg(name, cons.first)?;
+        if number.numeric_cmp(&best_number) == Some(wanted) {
+            best = cons.first;
+            best_number = number;
+        }
+        rest = cons.rest;
+    }
+    Ok(best)
+}
+
+fn subtract(a: Number, b: Number) -> Result<Number, BuiltinError> {
+    Ok(match a.promote(b) {
+        Promoted::Integers(a, b) => Number::from_i128(a as i128 - b as i128),
+        Promoted::Bigs(a, b) => Number::from_bigint(a.sub(&b)),
+        Promoted::Floats(a, b) => Number::Float(a - b),
+    })
+}
+
+fn divide(a: Number, b: Number) -> Result<Number, BuiltinError> {
+    Ok(match a.promote(b) {
+        Promoted::Integers(_, 0) => {
+            return Err(BuiltinError::BadArgument("/: division by zero".into()))
+        }
+        Promoted::Integers(a, b) if a % b == 0 => Number::from_i128(a as i128 / b as i128),
+        Promoted::Integers(a, b) => Number::Float(a as f64 / b as f64),
+        Promoted::Bigs(a, b) => match a.div_rem(&b) {
+            None => return Err(BuiltinError::BadArgument("/: division by zero".into())),
+            Some((quotient, remainder)) if remainder.is_zero() => Number::from_bigint(quotient),
+            Some(_) => Number::Float(a.div_to_f64(&b)),
+        },
+        Promoted::Floats(a, b) => Number::Float(a / b),
+    })
+}
+
+fn number_arg(name: &str, arg: PackedValue) -> Result<Number, BuiltinError> {
+    unpack_number(arg).map_err(|_| {
+        BuiltinError::BadArgument(format!("{}: {} is not number", name, unsafe {
+            arg.unguard()
+        }))
+    })
+}
+
+fn split_first<'a>(
+    name: &str,
+    args: PackedValue<'a>,
+) -> Result<(Number, PackedValue<'a>), BuiltinError> {
+    let cons = unpack_cons(args).map_err(|_| BuiltinError::NotEnoughArguments {
+        string: name.into(),
+        expected: 1,
+        provided: 0,
+    })?;
+    Ok((number_arg(name, cons.first)?, cons.rest))
+}
+
+/// Folds `op` over a list of numeric arguments, starting from `init`
+fn reduce<F: Fn(Number, Number) -> Result<Number, BuiltinError>>(
+    name: &str,
+    init: Number,
+    mut args: PackedValue,
+    op: F,
+) -> Result<Number, BuiltinError> {
+    let mut accum = init;
+    while let Ok(cons) = unpack_cons(args) {
+        accum = op(accum, number_arg(name, cons.first)?)?;
+        args = cons.rest;
+    }
+    Ok(accum)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::eval_to_string;
+
+    #[test]
+    fn test_overflow_promotes() {
+        assert_eq!(
+            eval_to_string("(* 2305843009213693951 4)"),
+            "9223372036854775804"
+        );
+        assert_eq!(
+            eval_to_string("(- (+ 2305843009213693951 1) 1)"),
+            "2305843009213693951"
+        );
+        assert_eq!(
+            eval_to_string("(abs -2305843009213693952)"),
+            "2305843009213693952"
+        );
+    }
+
+    #[test]
+    fn test_mixed_arithmetic() {
+        assert_eq!(eval_to_string("(+ 1 2.5)"), "3.5");
+        assert_eq!(eval_to_string("(/ 6 3)"), "2");
+        assert_eq!(eval_to_string("(/ 1 4)"), "0.25");
+        assert_eq!(eval_to_string("(mod -7 3)"), "2");
+        assert_eq!(eval_to_string("(max 1 2.5 2)"), "2.5");
+    }
+
+    #[test]
+    fn test_signed_literals() {
+        assert_eq!(eval_to_string("(+ +1 -2 +0x10)"), "15");
+        assert_eq!(eval_to_string("(- 3)"), "-3");
+        assert_eq!(eval_to_string("(- +2.5)"), "-2.5");
+    }
+
+    #[test]
+    fn test_huge_division() {
+        // both operands are past the largest f64
+        let big = format!("1{}", "0".repeat(400));
+        assert_eq!(eval_to_string(&format!("(/ (+ {big} 1) {big})")), "1.0");
+        assert_eq!(eval_to_string(&format!("(/ {big} (* 4 {big}0))")), "0.025");
+        assert_eq!(
+            eval_to_string(&format!("(/ (- {big}) (+ {big} {big} 1))")),
+            "-0.5"
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(eval_to_string("(< 1 2 3)"), "t");
+        assert_eq!(eval_to_string("(< 1 3 2)"), "()");
+        assert_eq!(eval_to_string("(= 1 1.0)"), "t");
+        assert_eq!(eval_to_string("(>= 100000000000000000000 1)"), "t");
+    }
+}
@@ -1,33 +1,51 @@
 use crate::{
-    builtins::{unpack::unpack_obj, BuiltinError},
+    builtins::{alist::rust_zip_alist, eval::rust_eval, unpack::unpack_cons, BuiltinError},
     def_builtin, let_slot,
-    value::Cons,
+    root::{Root, Slot},
+    thread::MutatorCtx,
+    value::{PackedValue, Value},
 };
 
 def_builtin!(closure(ctx, out) [&rest args] {
     let out = internal::closure_arg_check(ctx, out, args)?.slot();
-    Ok(out.root(&args).prepend_obj(ctx, &ctx.common_symbols.closure))
+    Ok(out.root(&args).prepend_obj(ctx, &ctx.common_symbols.closure)?)
 });
 
 def_builtin!(closure_apply(ctx, out) [closure_data, &rest args] {
-    let_slot!(ctx:flipped);
-    let flipped = flipped.alloc_cons(ctx, Cons { first: args, rest: closure_data });
-    internal::closure_apply_internal(ctx, out, flipped.value())
+    let_slot!(ctx:bound);
+    let (bound, body) = rust_closure_bind(ctx, bound, closure_data, args)?;
+    rust_eval(ctx, out, body, bound.value())
 });
 
+/// The `(bv fv body)` list of a closure object, or `None` for anything else
+pub fn closure_data<'a>(ctx: &MutatorCtx, value: PackedValue<'a>) -> Option<PackedValue<'a>> {
+    match value.unpack() {
+        Value::Object(obj) if obj.first == ctx.common_symbols.closure => Some(obj.rest),
+        _ => None,
+    }
+}
+
+/// Binds the closure's parameters to `args` on top of its captured scope and
+/// returns that scope with the body, leaving the caller to evaluate the body
+/// (in tail position, in the case of `rust_eval`)
+pub fn rust_closure_bind<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    out: Slot<'o>,
+    closure_data: PackedValue<'a>,
+    args: PackedValue<'a>,
+) -> Result<(Root<'o>, PackedValue<'a>), BuiltinError> {
+    let out = internal::closure_arg_check(ctx, out, closure_data)?.slot();
+    let bv = unpack_cons(closure_data).unwrap();
+    let fv = unpack_cons(bv.rest).unwrap();
+    let body = unpack_cons(fv.rest).unwrap();
+    let bound = rust_zip_alist(ctx, out, fv.first, args, bv.first)?;
+    Ok((bound, body.first))
+}
+
 mod internal {
-    use crate::{
-        builtins::{alist::rust_zip_alist, eval::rust_eval, list::rust_len, BuiltinError},
-        def_builtin, let_slot,
-        value::Cons,
-    };
+    use crate::def_builtin;
+
     def_builtin!(closure_arg_check(ctx, out) [bv: listp, fv: listp, body] {
         Ok(out.nil())
     });
-
-    def_builtin!(closure_apply_internal(ctx, out) [args, bv: listp, fv: listp, body] {
-        let_slot!(ctx:bound);
-        let mut bound = rust_zip_alist(ctx, bound, fv, args, bv)?;
-        rust_eval(ctx, out, body, bound.value())
-    });
 }
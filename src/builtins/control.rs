@@ -1,7 +1,9 @@
 use crate::{
     builtins::{eval::rust_eval, list::rust_concat, BuiltinError},
     def_builtin, let_slot,
-    value::Value,
+    root::{Root, Slot},
+    thread::MutatorCtx,
+    value::{PackedValue, Value},
 };
 
 use super::unpack::unpack_cons;
@@ -19,7 +21,7 @@ def_builtin!(with(ctx, out) [alist, closure] {
 
     let_slot!(ctx:new_scope);
     let new_scope = rust_concat(ctx, new_scope, alist, bv)?;
-    Ok(out.nil().prepend(ctx, &body).prepend(ctx, &fv).prepend(ctx, &bv).prepend_obj(ctx, &ctx.common_symbols.closure))
+    Ok(out.nil().prepend(ctx, &body)?.prepend(ctx, &fv)?.prepend(ctx, &bv)?.prepend_obj(ctx, &ctx.common_symbols.closure)?)
 });
 
 // def_builtin!(let__(ctx, out) [scope, list, body] {
@@ -43,9 +45,9 @@ def_builtin!(bind(ctx, out) [scope, &rest alist] {
         };
 
         eval_out = rust_eval(ctx, eval_out.slot(), expr, scope)?;
-        eval_out = eval_out.prepend(ctx, &key);
+        eval_out = eval_out.prepend(ctx, &key)?;
 
-        out = out.prepend(ctx, &eval_out.value());
+        out = out.prepend(ctx, &eval_out.value())?;
 
         alist = cons.rest;
     }
@@ -67,11 +69,229 @@ def_builtin!(bind_star(ctx, out) [scope, &rest alist] {
         };
 
         eval_out = rust_eval(ctx, eval_out.slot(), expr, out.value())?;
-        eval_out = eval_out.prepend(ctx, &key);
+        eval_out = eval_out.prepend(ctx, &key)?;
 
-        out = out.prepend(ctx, &eval_out.value());
+        out = out.prepend(ctx, &eval_out.value())?;
 
         alist = cons.rest;
     }
     Ok(out)
 });
+
+/// What remains of a special form once its non-tail parts are evaluated:
+/// either a finished value or a form to evaluate into the returned slot
+pub enum Tail<'o, 'a> {
+    Done(Root<'o>),
+    Eval(Slot<'o>, PackedValue<'a>),
+}
+
+pub fn is_special_form(ctx: &MutatorCtx, symbol: PackedValue) -> bool {
+    let s = ctx.common_symbols;
+    [s._if, s.cond, s.and, s.or, s.when, s.unless].contains(&symbol)
+}
+
+pub fn rust_eval_special<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    out: Slot<'o>,
+    form: PackedValue<'a>,
+    scope: PackedValue<'a>,
+    args: PackedValue<'a>,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    let s = ctx.common_symbols;
+    if form == s._if {
+        eval_if(ctx, out, scope, args)
+    } else if form == s.cond {
+        eval_cond(ctx, out, scope, args)
+    } else if form == s.and {
+        eval_and(ctx, out, scope, args)
+    } else if form == s.or {
+        eval_or(ctx, out, scope, args)
+    } else if form == s.when {
+        eval_when(ctx, out, scope, args, true)
+    } else if form == s.unless {
+        eval_when(ctx, out, scope, args, false)
+    } else {
+        unreachable!("checked by is_special_form")
+    }
+}
+
+fn eval_truthy(
+    ctx: &MutatorCtx,
+    scope: PackedValue,
+    form: PackedValue,
+) -> Result<bool, BuiltinError> {
+    let_slot!(ctx: test_out);
+    let test_out = rust_eval(ctx, test_out, form, scope)?;
+    Ok(test_out.value() != Value::Nil.pack())
+}
+
+/// Evaluates every form but the last, which is left in tail position
+fn eval_sequence<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    out: Slot<'o>,
+    scope: PackedValue<'a>,
+    mut body: PackedValue<'a>,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    let_slot!(ctx: discard);
+    let mut discard = discard.nil();
+    while let Ok(cons) = unpack_cons(body) {
+        if cons.rest == Value::Nil.pack() {
+            return Ok(Tail::Eval(out, cons.first));
+        }
+        discard = rust_eval(ctx, discard.slot(), cons.first, scope)?;
+        body = cons.rest;
+    }
+    Ok(Tail::Done(out.nil()))
+}
+
+fn eval_if<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    out: Slot<'o>,
+    scope: PackedValue<'a>,
+    args: PackedValue<'a>,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    let not_enough = |provided| BuiltinError::NotEnoughArguments {
+        string: "if".into(),
+        expected: 2,
+        provided,
+    };
+    let test = unpack_cons(args).map_err(|_| not_enough(0))?;
+    let then = unpack_cons(test.rest).map_err(|_| not_enough(1))?;
+    let otherwise = match unpack_cons(then.rest) {
+        Ok(cons) if cons.rest != Value::Nil.pack() => {
+            return Err(BuiltinError::TooManyArguments {
+                string: "if".into(),
+                expected: 3,
+            })
+        }
+        Ok(cons) => cons.first,
+        Err(_) => Value::Nil.pack(),
+    };
+
+    if eval_truthy(ctx, scope, test.first)? {
+        Ok(Tail::Eval(out, then.first))
+    } else {
+        Ok(Tail::Eval(out, otherwise))
+    }
+}
+
+fn eval_when<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    out: Slot<'o>,
+    scope: PackedValue<'a>,
+    args: PackedValue<'a>,
+    expected: bool,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    let test = unpack_cons(args).map_err(|_| BuiltinError::NotEnoughArguments {
+        string: if expected { "when" } else { "unless" }.into(),
+        expected: 1,
+        provided: 0,
+    })?;
+
+    if eval_truthy(ctx, scope, test.first)? == expected {
+        eval_sequence(ctx, out, scope, test.rest)
+    } else {
+        Ok(Tail::Done(out.nil()))
+    }
+}
+
+fn eval_cond<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    mut out: Slot<'o>,
+    scope: PackedValue<'a>,
+    mut clauses: PackedValue<'a>,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    while let Ok(cons) = unpack_cons(clauses) {
+        let clause = unpack_cons(cons.first).map_err(|_| {
+            BuiltinError::BadArgument(format!("cond: {} is not a clause", unsafe {
+                cons.first.unguard()
+            }))
+        })?;
+
+        let test_out = rust_eval(ctx, out, clause.first, scope)?;
+        if test_out.value() != Value::Nil.pack() {
+            // a clause without a body yields the value of its test
+            if clause.rest == Value::Nil.pack() {
+                return Ok(Tail::Done(test_out));
+            }
+            return eval_sequence(ctx, test_out.slot(), scope, clause.rest);
+        }
+
+        out = test_out.slot();
+        clauses = cons.rest;
+    }
+    Ok(Tail::Done(out.nil()))
+}
+
+fn eval_and<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    out: Slot<'o>,
+    scope: PackedValue<'a>,
+    mut forms: PackedValue<'a>,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    while let Ok(cons) = unpack_cons(forms) {
+        if cons.rest == Value::Nil.pack() {
+            return Ok(Tail::Eval(out, cons.first));
+        }
+        if !eval_truthy(ctx, scope, cons.first)? {
+            return Ok(Tail::Done(out.nil()));
+        }
+        forms = cons.rest;
+    }
+    Ok(Tail::Done(out.root(&ctx.common_symbols.t)))
+}
+
+fn eval_or<'o, 'a>(
+    ctx: &'o MutatorCtx,
+    mut out: Slot<'o>,
+    scope: PackedValue<'a>,
+    mut forms: PackedValue<'a>,
+) -> Result<Tail<'o, 'a>, BuiltinError> {
+    while let Ok(cons) = unpack_cons(forms) {
+        if cons.rest == Value::Nil.pack() {
+            return Ok(Tail::Eval(out, cons.first));
+        }
+        let value = rust_eval(ctx, out, cons.first, scope)?;
+        if value.value() != Value::Nil.pack() {
+            return Ok(Tail::Done(value));
+        }
+        out = value.slot();
+        forms = cons.rest;
+    }
+    Ok(Tail::Done(out.nil()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::eval_to_string;
+
+    #[test]
+    fn test_if() {
+        assert_eq!(eval_to_string("(if (< 1 2) 'yes 'no)"), "yes");
+        assert_eq!(eval_to_string("(if () 'yes 'no)"), "no");
+        assert_eq!(eval_to_string("(if () 'yes)"), "()");
+        assert_eq!(eval_to_string("(if 0 'yes 'no)"), "yes");
+    }
+
+    #[test]
+    fn test_cond() {
+        assert_eq!(
+            eval_to_string("(cond ((> 1 2) 'a) ((< 1 2) 'b 'c) (t 'd))"),
+            "c"
+        );
+        assert_eq!(eval_to_string("(cond (() 'a) (3))"), "3");
+        assert_eq!(eval_to_string("(cond (() 'a))"), "()");
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        // the undefined symbol would fail if it were evaluated
+        assert_eq!(eval_to_string("(and 1 () undefined)"), "()");
+        assert_eq!(eval_to_string("(and 1 2 3)"), "3");
+        assert_eq!(eval_to_string("(and)"), "t");
+        assert_eq!(eval_to_string("(or () 2 undefined)"), "2");
+        assert_eq!(eval_to_string("(or)"), "()");
+        assert_eq!(eval_to_string("(when (= 1 1) 1 2)"), "2");
+        assert_eq!(eval_to_string("(unless (= 1 1) undefined)"), "()");
+    }
+}
@@ -1,78 +1,151 @@
 use crate::builtins::alist::rust_assq;
+use crate::builtins::control::{is_special_form, rust_eval_special, Tail};
 use crate::builtins::func::rust_map_eval;
 use crate::builtins::quasiquote::rust_eval_quasiquote;
 use crate::builtins::unpack::unpack_cons;
 use crate::object::TagType;
+use crate::root::{Root, Slot};
+use crate::thread::MutatorCtx;
 use crate::value::Value;
 use crate::{def_builtin, let_slot};
 
 use super::alist::assq;
-use super::closure::rust_closure_apply;
+use super::closure::{closure_data, rust_closure_apply, rust_closure_bind};
 use super::types::rust::*;
-use super::BuiltinError;
+use super::{BuiltinError, BuiltinResult};
 
 def_builtin!(eval(ctx, out) [code, scope: listp] {
-    // unsafe { println!("EVAL: {}", code.unguard()); };
-    match code.unpack() {
-        Value::Cons(ptr) => {
-            if !proper_list_p(code) {
-                return Err(BuiltinError::BadArgument("code contains an improper list".into()))
-            }
+    let _depth = ctx.enter_eval()?;
 
-            let left = ptr.first;
-            let right = ptr.rest;
+    let_slot!(ctx: code_root, ctx: scope_root);
+    let mut code_root = code_root.root(&code);
+    let mut scope_root = scope_root.root(&scope);
+    eval_loop(ctx, out, &mut code_root, &mut scope_root)
+        .map_err(|err| err.located(ctx, code_root.value()))
+});
 
-            if left == ctx.common_symbols.quote {
-                return Ok(out.root(&unpack_cons(right).map_err(|_| BuiltinError::NotEnoughArguments { string: "quote".into(), expected: 1, provided: 0 })?.first));
-            } else if left == ctx.common_symbols.quasiquote {
-                return rust_eval_quasiquote(ctx, out, scope, unpack_cons(right).map_err(|_| BuiltinError::NotEnoughArguments { string: "quote".into(), expected: 1, provided: 0 })?.first, Value::Integer(1).pack());
-            }
+// Forms in tail position replace `code_root` and `scope_root` and go around the
+// loop instead of recursing, so tail calls run in constant Rust stack space.
+// On error, `code_root` holds the form that failed.
+fn eval_loop<'o>(
+    ctx: &'o MutatorCtx,
+    mut out: Slot<'o>,
+    code_root: &mut Root,
+    scope_root: &mut Root,
+) -> BuiltinResult<'o> {
+    loop {
+        // the interpreter's back-edge, so that other threads waiting to
+        // collect need not wait for the next allocation
+        ctx.alloc.safepoint();
+        let code = code_root.value();
+        let scope = scope_root.value();
+        // unsafe { println!("EVAL: {}", code.unguard()); };
+        match code.unpack() {
+            Value::Cons(ptr) => {
+                if !proper_list_p(code) {
+                    return Err(BuiltinError::BadArgument(
+                        "code contains an improper list".into(),
+                    ));
+                }
 
-            let_slot!(ctx:left_out);
-            let left_out = rust_eval(ctx, left_out, left, scope)?;
-
-            if let Value::Object(ptr) = left_out.value().unpack() {
-                if ptr.first == ctx.common_symbols.fexpr {
-                    let_slot!(ctx:fexpr_arg);
-                    let arg_root = fexpr_arg.root(&right).prepend(ctx, &scope);
-                    return rust_apply(ctx, out, ptr.rest, arg_root.value());
-                } else if ptr.first == ctx.common_symbols._macro {
-                    let_slot!(ctx:macro_out);
-                    let macro_out = rust_apply(ctx, macro_out, ptr.rest, right)?;
-                    unsafe { println!("{}", macro_out.value().unguard()) }
-                    return rust_eval(ctx, out, macro_out.value(), scope);
+                let left = ptr.first;
+                let right = ptr.rest;
+
+                if left == ctx.common_symbols.quote {
+                    return Ok(out.root(
+                        &unpack_cons(right)
+                            .map_err(|_| BuiltinError::NotEnoughArguments {
+                                string: "quote".into(),
+                                expected: 1,
+                                provided: 0,
+                            })?
+                            .first,
+                    ));
+                } else if left == ctx.common_symbols.quasiquote {
+                    return rust_eval_quasiquote(
+                        ctx,
+                        out,
+                        scope,
+                        unpack_cons(right)
+                            .map_err(|_| BuiltinError::NotEnoughArguments {
+                                string: "quote".into(),
+                                expected: 1,
+                                provided: 0,
+                            })?
+                            .first,
+                        Value::Integer(1).pack(),
+                    );
+                } else if is_special_form(ctx, left) {
+                    match rust_eval_special(ctx, out, left, scope, right)? {
+                        Tail::Done(root) => return Ok(root),
+                        Tail::Eval(slot, next) => {
+                            let next = unsafe { next.unguard() };
+                            out = slot;
+                            code_root.set_raw(next);
+                            continue;
+                        }
+                    }
                 }
-            }
 
-            let_slot!(ctx:map_out);
-            let map_out = rust_map_eval(ctx, map_out, scope, right)?;
+                let_slot!(ctx:left_out);
+                let left_out = rust_eval(ctx, left_out, left, scope)?;
+                let mut callee = left_out.value();
 
-            let res = rust_apply(ctx, out, left_out.value(), map_out.value());
+                let_slot!(ctx:args_out);
+                let args_out = match callee.unpack() {
+                    Value::Object(ptr) if ptr.first == ctx.common_symbols.fexpr => {
+                        callee = ptr.rest;
+                        args_out.root(&right).prepend(ctx, &scope)?
+                    }
+                    Value::Object(ptr) if ptr.first == ctx.common_symbols._macro => {
+                        let macro_out = rust_apply(ctx, args_out, ptr.rest, right)?;
+                        let expansion = unsafe { macro_out.packed() };
+                        code_root.set_raw(expansion);
+                        continue;
+                    }
+                    _ => rust_map_eval(ctx, args_out, scope, right)?,
+                };
 
-            if res.is_err() {
-                unsafe { println!("{}", left.unguard()) }
-                // unsafe { println!("{}", left_out.value().unguard()) }
-            }
+                if let Some(closure_data) = closure_data(ctx, callee) {
+                    let_slot!(ctx: bound);
+                    let (bound, body) =
+                        rust_closure_bind(ctx, bound, closure_data, args_out.value())?;
+                    let body = unsafe { body.unguard() };
+                    scope_root.set_raw(unsafe { bound.packed() });
+                    code_root.set_raw(body);
+                    continue;
+                }
 
-            res
-        }
-        Value::Symbol(ptr) => {
-            let_slot!(ctx:assq_out);
-            let assq_out = rust_assq(ctx, assq_out, code, scope)?;
-            match assq_out.value().unpack() {
-                Value::Cons(ptr) => Ok(out.root(&ptr.rest)),
-                _ => {
-                    // unsafe { println!("scope: {}", scope.unguard()) }
-                    Err(BuiltinError::UndefinedSymbol(ptr.to_string()))
+                return rust_apply(ctx, out, callee, args_out.value());
+            }
+            Value::Symbol(ptr) => {
+                if code == ctx.common_symbols.t {
+                    return Ok(out.root(&code));
                 }
+
+                let_slot!(ctx:assq_out);
+                let assq_out = rust_assq(ctx, assq_out, code, scope)?;
+                return match assq_out.value().unpack() {
+                    Value::Cons(ptr) => Ok(out.root(&ptr.rest)),
+                    _ => {
+                        // unsafe { println!("scope: {}", scope.unguard()) }
+                        Err(BuiltinError::UndefinedSymbol(ptr.to_string()))
+                    }
+                };
+            }
+            Value::Nil
+            | Value::Integer(_)
+            | Value::Float(_)
+            | Value::Bigint(_)
+            | Value::Function(_)
+            | Value::Object(_)
+            | Value::String(_) => {
+                // self-evaluating forms
+                return Ok(out.root(&code));
             }
-        }
-        Value::Nil | Value::Integer(_) | Value::Function(_) | Value::Object(_) => {
-            // self-evaluating forms
-            Ok(out.root(&code))
         }
     }
-});
+}
 
 def_builtin!(apply(ctx, out) [left, right] {
     match left.unpack() {
@@ -101,7 +174,10 @@ def_builtin!(apply(ctx, out) [left, right] {
 #[cfg(test)]
 mod test {
     use crate::{
-        builtins::eval::eval,
+        builtins::{
+            eval::{eval, rust_eval},
+            BuiltinError,
+        },
         let_slot,
         root::{Root, Slot},
         thread::MutatorCtx,
@@ -110,8 +186,7 @@ mod test {
 
     macro_rules! eval_test {
         (| $ctx:ident, $args:ident | $args_block:block, | $out:ident | $out_block:block) => {
-            let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
-            let ctx = crate::thread::MutatorCtx::new_from_global(global);
+            let ctx = crate::builtins::stress_ctx(1);
 
             let_slot!(ctx: $args);
             let $ctx = &ctx;
@@ -128,8 +203,13 @@ mod test {
     fn eval_nil() {
         eval_test!(
             |ctx, args| {
-                args.nil()
-                    .map(|slot, root| slot.nil().singleton(ctx).prepend(ctx, &root.value()))
+                args.nil().map(|slot, root| {
+                    slot.nil()
+                        .singleton(ctx)
+                        .unwrap()
+                        .prepend(ctx, &root.value())
+                        .unwrap()
+                })
             },
             |out| { assert!(out.value() == Value::Nil.pack()) }
         );
@@ -151,14 +231,150 @@ mod test {
                                 rest: Value::Integer(2).pack(),
                             },
                         )
+                        .unwrap()
                     })
                     .singleton(ctx)
+                    .unwrap()
                     .singleton(ctx)
-                    .prepend(ctx, &code.value());
+                    .unwrap()
+                    .prepend(ctx, &code.value())
+                    .unwrap();
                 drop(code);
                 args
             },
             |out| { assert!(out.value() == Value::Integer(2).pack()) }
         );
     }
+
+    #[test]
+    fn eval_tail_calls() {
+        // deep enough to overflow the native stack if each call recursed, and
+        // too many allocations to collect before every one
+        let out = crate::builtins::eval_to_string_in(
+            &crate::builtins::stress_ctx(64),
+            "((lambda (count) (count count 20000 0))
+              (lambda (self n acc)
+                (if (= n 0) acc (self self (- n 1) (+ acc 1)))))",
+        );
+        assert_eq!(out, "20000");
+    }
+
+    #[test]
+    fn eval_depth_limit() {
+        let ctx = crate::builtins::stress_ctx(1);
+        ctx.max_eval_depth.set(200);
+
+        let_slot!(ctx: scope);
+        let scope = crate::builtins::core(&ctx, scope);
+
+        let_slot!(ctx: code);
+        let code = crate::parse::parse(
+            "((lambda (f) (f f 1000))
+              (lambda (self n) (if (= n 0) 0 (+ 1 (self self (- n 1))))))",
+            &ctx,
+            code,
+        )
+        .unwrap();
+
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, code.value(), scope.value()) {
+            Err(BuiltinError::StackOverflow(depth)) => assert_eq!(depth, 200),
+            _ => panic!("expected a stack overflow"),
+        }
+        assert_eq!(ctx.eval_depth.get(), 0);
+    }
+
+    #[test]
+    fn eval_default_depth_limit() {
+        // test threads get the ordinary stack size, which the default limit
+        // must fit within
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: scope);
+        let scope = crate::builtins::core(&ctx, scope);
+
+        let_slot!(ctx: code);
+        let code = crate::parse::parse(
+            "((lambda (f) (f f 100000))
+              (lambda (self n) (if (= n 0) 0 (+ 1 (self self (- n 1))))))",
+            &ctx,
+            code,
+        )
+        .unwrap();
+
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, code.value(), scope.value()) {
+            Err(BuiltinError::StackOverflow(depth)) => {
+                assert_eq!(depth, crate::thread::DEFAULT_MAX_EVAL_DEPTH)
+            }
+            _ => panic!("expected a stack overflow"),
+        }
+    }
+
+    #[test]
+    fn eval_out_of_memory() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        // collecting on every allocation would take minutes to fill the heap
+        ctx.alloc.set_stress_gc(Some(1024));
+
+        let_slot!(ctx: scope);
+        let scope = crate::builtins::core(&ctx, scope);
+        global
+            .alloc_state
+            .lock()
+            .unwrap()
+            .set_max_heap_size(Some(2 * 1024 * 1024));
+
+        let_slot!(ctx: code);
+        let code = crate::parse::parse(
+            "((lambda (grow) (grow grow 0 ()))
+              (lambda (self n acc)
+                (if (= n 1000000) n (self self (+ n 1) (cons n acc)))))",
+            &ctx,
+            code,
+        )
+        .unwrap();
+
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, code.value(), scope.value()) {
+            Err(BuiltinError::OutOfMemory) => (),
+            _ => panic!("expected to run out of memory"),
+        }
+
+        // the list built before failing is garbage now, so a smaller one fits
+        let code = crate::parse::parse(
+            "((lambda (grow) (grow grow 0 ()))
+              (lambda (self n acc)
+                (if (= n 10000) n (self self (+ n 1) (cons n acc)))))",
+            &ctx,
+            code.slot(),
+        )
+        .unwrap();
+        let_slot!(ctx: out);
+        let out = rust_eval(&ctx, out, code.value(), scope.value()).unwrap();
+        assert!(out.value() == Value::Integer(10000).pack());
+    }
+
+    #[test]
+    fn eval_error_location() {
+        let ctx = crate::builtins::stress_ctx(1);
+
+        let_slot!(ctx: scope);
+        let scope = crate::builtins::core(&ctx, scope);
+
+        let_slot!(ctx: code);
+        let code =
+            crate::parse::parse_program("(+ 1\n   (car x))", "test.lisp", &ctx, code).unwrap();
+        let form = crate::builtins::unpack::unpack_cons(code.value())
+            .unwrap()
+            .first;
+
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, form, scope.value()) {
+            Err(err) => assert_eq!(err.to_string(), "test.lisp:2:4: undefined symbol car"),
+            Ok(_) => panic!("expected an undefined symbol"),
+        }
+    }
 }
@@ -12,7 +12,7 @@ def_builtin!(fold(ctx, out) [func, list, init] {
     let mut arg_list = arg_list.nil();
 
     while let Ok(cons) = unpack_cons(list) {
-        arg_list = arg_list.slot().nil().prepend(ctx, &accum.value()).prepend(ctx, &cons.first);
+        arg_list = arg_list.slot().nil().prepend(ctx, &accum.value())?.prepend(ctx, &cons.first)?;
         accum = rust_apply(ctx, accum.slot(), func, arg_list.value())?;
         list = cons.rest;
     }
@@ -34,7 +34,7 @@ def_builtin!(foldr(ctx, out) [func, list, init] {
     }
 
     while let Some(item) = stack.pop() {
-        arg_list = arg_list.slot().nil().prepend(ctx, &accum.value()).prepend(ctx, &item);
+        arg_list = arg_list.slot().nil().prepend(ctx, &accum.value())?.prepend(ctx, &item)?;
         accum = rust_apply(ctx, accum.slot(), func, arg_list.value())?;
     }
 
@@ -59,9 +59,9 @@ def_builtin!(map(ctx, out) [func, list] {
     }
 
     while let Some(item) = stack.pop() {
-        arg_list = arg_list.slot().nil().prepend(ctx, &item);
+        arg_list = arg_list.slot().nil().prepend(ctx, &item)?;
         f_out = rust_apply(ctx, f_out.slot(), func, arg_list.value())?;
-        accum = accum.prepend(ctx, &f_out.value());
+        accum = accum.prepend(ctx, &f_out.value())?;
     }
 
     Ok(accum)
@@ -82,7 +82,7 @@ def_builtin!(map_eval(ctx, out) [scope, list] {
 
     while let Some(item) = stack.pop() {
         f_out = rust_eval(ctx, f_out.slot(), item, scope)?;
-        accum = accum.prepend(ctx, &f_out.value());
+        accum = accum.prepend(ctx, &f_out.value())?;
     }
 
     Ok(accum)
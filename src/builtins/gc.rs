@@ -0,0 +1,87 @@
+use crate::{def_builtin, let_slot, value::Cons, value::Value};
+
+def_builtin!(gc(ctx, out) [] {
+    ctx.alloc.gc();
+    Ok(out.nil())
+});
+
+// Pauses are in microseconds, sizes in bytes. Increments are the pauses of
+// incremental collections.
+def_builtin!(gc_stats(ctx, out) [] {
+    let stats = ctx.alloc.gc_stats();
+    let fields = [
+        ("collections", stats.collections),
+        ("full-collections", stats.full_collections),
+        ("increments", stats.increments),
+        ("total-pause", stats.total_pause.as_micros() as usize),
+        ("max-pause", stats.max_pause.as_micros() as usize),
+        ("last-pause", stats.last_pause.as_micros() as usize),
+        ("bytes-allocated", stats.bytes_allocated),
+        ("live-bytes", stats.live_bytes),
+        ("blocks-in-use", stats.blocks.in_use),
+        ("blocks-free", stats.blocks.free),
+        ("large-objects", stats.blocks.large_objects),
+        ("fragmented-blocks", stats.fragmented_blocks),
+        ("symbols", stats.symbols),
+    ];
+
+    let mut out = out.nil();
+    for (name, n) in fields.iter().rev() {
+        let_slot!(ctx: key, ctx: entry);
+        let key = key.intern(ctx, name.to_string());
+        let entry = entry.alloc_cons(ctx, Cons { first: key.value(), rest: Value::Integer(*n as isize).pack() })?;
+        out = out.prepend(ctx, &entry.value())?;
+    }
+    Ok(out)
+});
+
+#[cfg(test)]
+mod test {
+    use crate::builtins::{eval_to_string, eval_to_string_in};
+
+    #[test]
+    fn test_gc_stats() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        // stress collections would be counted too
+        ctx.alloc.set_stress_gc(None);
+        assert_eq!(
+            eval_to_string_in(&ctx, "(unless (gc) (rest (assq 'collections (gc-stats))))"),
+            "1"
+        );
+        assert_eq!(
+            eval_to_string("(unless (gc) (> (rest (assq 'live-bytes (gc-stats))) 0))"),
+            "t"
+        );
+    }
+
+    #[test]
+    fn test_incremental_gc() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        global.alloc_state.lock().unwrap().set_incremental(Some(8));
+        // stress collections are whole, and would leave no increments to
+        // count; the spawned thread inherits the setting. Verifying also
+        // collects whole.
+        ctx.alloc.set_stress_gc(None);
+        global.alloc_state.lock().unwrap().set_verify(false);
+
+        // a producer thread sends lists over a channel, written through the
+        // barrier, while the consumer sums them and marking goes on
+        let out = eval_to_string_in(
+            &ctx,
+            "((lambda (ch produce drain)
+                ((lambda (producer)
+                   (list (> (rest (assq 'increments (gc-stats))) 0)
+                         (join producer)
+                         (drain drain ch 0)))
+                 (spawn produce produce ch 300)))
+              (make-channel)
+              (lambda (self ch n)
+                (if (= n 0) (close ch) (and (send ch (list n n)) (self self ch (- n 1)))))
+              (lambda (self ch acc)
+                ((lambda (v) (if v (self self ch (+ acc (first v))) acc)) (recv ch))))",
+        );
+        assert_eq!(out, "(t t 45150)");
+    }
+}
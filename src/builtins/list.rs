@@ -27,7 +27,7 @@ def_builtin!(rest(ctx, out) [list: listp] {
 });
 
 def_builtin!(cons(ctx, out) [first, rest] {
-    Ok(out.alloc_cons(ctx, Cons { first, rest }))
+    Ok(out.alloc_cons(ctx, Cons { first, rest })?)
 });
 
 def_builtin!(list(ctx, out) [&rest list] {
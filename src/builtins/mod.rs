@@ -1,18 +1,26 @@
 pub mod alist;
+pub mod arith;
 pub mod closure;
 pub mod control;
 pub mod eval;
 pub mod func;
+pub mod gc;
 pub mod list;
 pub mod obj;
 pub mod quasiquote;
+pub mod spawn;
+pub mod sync;
 pub mod tree;
 pub mod types;
 pub mod unpack;
 
+use std::fmt::Display;
+
 use crate::{
+    alloc::AllocError,
     object::{PackedPtr, RawCons, TagType, UnpackedPtr},
     root::{Gc, Root, Slot},
+    source::SourcePos,
     thread::MutatorCtx,
     value::{Cons, PackedValue},
 };
@@ -33,6 +41,60 @@ pub enum BuiltinError {
         string: String,
         expected: usize,
     },
+    StackOverflow(usize),
+    /// An allocation would have grown the heap past its maximum size
+    OutOfMemory,
+    /// A thread started by `spawn` failed, with the error it failed with
+    ThreadFailed(String),
+    /// An error raised while evaluating the form read from `SourcePos`
+    Located(SourcePos, Box<BuiltinError>),
+}
+
+impl BuiltinError {
+    /// Attaches the position `form` was read from, unless an inner form already did
+    pub fn located(self, ctx: &MutatorCtx, form: PackedValue) -> Self {
+        if let BuiltinError::Located(..) = self {
+            return self;
+        }
+        match ctx.alloc.source_pos(unsafe { form.unguard() }) {
+            Some(pos) => BuiltinError::Located(pos, Box::new(self)),
+            None => self,
+        }
+    }
+}
+
+impl From<AllocError> for BuiltinError {
+    fn from(_: AllocError) -> Self {
+        BuiltinError::OutOfMemory
+    }
+}
+
+impl Display for BuiltinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuiltinError::NotCallable(string, tag) => write!(f, "{} ({:?})", string, tag),
+            BuiltinError::BadArgument(string) => write!(f, "{}", string),
+            BuiltinError::UndefinedSymbol(name) => write!(f, "undefined symbol {}", name),
+            BuiltinError::NotEnoughArguments {
+                string,
+                expected,
+                provided,
+            } => write!(
+                f,
+                "{}: expected {} arguments, got {}",
+                string, expected, provided
+            ),
+            BuiltinError::TooManyArguments { string, expected } => {
+                write!(f, "{}: too many arguments, expected {}", string, expected)
+            }
+            BuiltinError::StackOverflow(depth) => {
+                write!(f, "stack overflow after {} nested evaluations", depth)
+            }
+            BuiltinError::OutOfMemory => write!(f, "out of memory"),
+            BuiltinError::ThreadFailed(err) => write!(f, "thread failed: {}", err),
+            BuiltinError::Located(pos, err) => write!(f, "{}: {}", pos, err),
+        }
+    }
 }
 
 pub type BuiltinFunction =
@@ -40,9 +102,9 @@ pub type BuiltinFunction =
 
 macro_rules! generate_scope {
     ($name:ident
-        functions: [$($function_mod:ident :: $function:ident $(/ $function_name:ident)?),*]
-        macros: [$($macro_mod:ident :: $macro:ident $(/ $macro_name:ident)?),*]
-        fexprs: [$($fexpr_mod:ident :: $fexpr:ident $(/ $fexpr_name:ident)?),*]
+        functions: [$($function_mod:ident :: $function:ident $(/ $function_name:tt)?),*]
+        macros: [$($macro_mod:ident :: $macro:ident $(/ $macro_name:tt)?),*]
+        fexprs: [$($fexpr_mod:ident :: $fexpr:ident $(/ $fexpr_name:tt)?),*]
     ) => {
         pub fn $name<'r>(ctx: &crate::thread::MutatorCtx, out: crate::root::Slot<'r>) -> crate::root::Root<'r> {
             let out = out.nil();
@@ -53,32 +115,32 @@ macro_rules! generate_scope {
             $(
                 #[allow(unused_variables)]
                 let function_name = stringify!($function).rsplit("::").next().unwrap();
-                $(let function_name = stringify!($function_name);)?
+                $(let function_name = stringify!($function_name).trim_matches('"');)?
                 let function_name = crate::util::rust_to_lisp_symbol(function_name);
 
                 let name = name.slot().intern(ctx, function_name);
-                let assoc = unsafe { assoc.slot().function($function_mod::$function) }.prepend(ctx, &name.value());
-                let out = out.prepend(ctx, &assoc.value());
+                let assoc = unsafe { assoc.slot().function($function_mod::$function) }.prepend(ctx, &name.value()).unwrap();
+                let out = out.prepend(ctx, &assoc.value()).unwrap();
             )*
             $(
                 #[allow(unused_variables)]
                 let macro_name = stringify!($macro).rsplit("::").next().unwrap();
-                $(let macro_name = stringify!($macro_name);)?
+                $(let macro_name = stringify!($macro_name).trim_matches('"');)?
                 let macro_name = crate::util::rust_to_lisp_symbol(macro_name);
 
                 let name = name.slot().intern(ctx, macro_name);
-                let assoc = unsafe { assoc.slot().function($macro_mod::$macro) }._macro(ctx).prepend(ctx, &name.value());
-                let out = out.prepend(ctx, &assoc.value());
+                let assoc = unsafe { assoc.slot().function($macro_mod::$macro) }._macro(ctx).unwrap().prepend(ctx, &name.value()).unwrap();
+                let out = out.prepend(ctx, &assoc.value()).unwrap();
             )*
             $(
                 #[allow(unused_variables)]
                 let fexpr_name = stringify!($fexpr).rsplit("::").next().unwrap();
-                $(let fexpr_name = stringify!($fexpr_name);)?
+                $(let fexpr_name = stringify!($fexpr_name).trim_matches('"');)?
                 let fexpr_name = crate::util::rust_to_lisp_symbol(fexpr_name);
 
                 let name = name.slot().intern(ctx, fexpr_name);
-                let assoc = unsafe { assoc.slot().function($fexpr_mod::$fexpr) }.fexpr(ctx).prepend(ctx, &name.value());
-                let out = out.prepend(ctx, &assoc.value());
+                let assoc = unsafe { assoc.slot().function($fexpr_mod::$fexpr) }.fexpr(ctx).unwrap().prepend(ctx, &name.value()).unwrap();
+                let out = out.prepend(ctx, &assoc.value()).unwrap();
             )*
             out
         }
@@ -88,14 +150,21 @@ macro_rules! generate_scope {
 generate_scope!(core
     functions: [
         eval::eval, eval::apply,
+        arith::add/"+", arith::sub/"-", arith::mul/"*", arith::div/"/", arith::modulo/"mod",
+        arith::abs, arith::min, arith::max,
+        arith::lt/"<", arith::le/"<=", arith::num_eq/"=", arith::ge/">=", arith::gt/">",
         list::first, list::rest, list::cons, list::list, list::nthrest, list::len, list::concat,
         obj::objfirst, obj::objrest, obj::obj,
         alist::assq,
-        types::listp, types::nilp, types::consp, types::proper_list_p, types::objp,
+        types::listp, types::nilp, types::consp, types::proper_list_p, types::objp, types::stringp, types::floatp, types::numberp,
         func::fold, func::foldr, func::map,
         closure::closure,
         control::with,
-        tree::bindex
+        tree::bindex,
+        gc::gc, gc::gc_stats,
+        spawn::spawn, spawn::join,
+        sync::make_channel, sync::send, sync::recv, sync::try_recv, sync::close,
+        sync::make_mutex, sync::lock, sync::unlock
     ]
 
     macros: [
@@ -107,3 +176,35 @@ generate_scope!(core
         control::bind, control::bind_star
     ]
 );
+
+/// A mutator on a fresh heap that collects before every `interval`th
+/// allocation, so that tests catch builtins that forget to root something
+#[cfg(test)]
+pub fn stress_ctx(interval: usize) -> crate::thread::MutatorCtx {
+    let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+    let ctx = crate::thread::MutatorCtx::new_from_global(global);
+    ctx.alloc.set_stress_gc(Some(interval));
+    ctx
+}
+
+/// Reads and evaluates `source` in a fresh core scope, printing the result
+#[cfg(test)]
+pub fn eval_to_string(source: &str) -> String {
+    let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+    let ctx = crate::thread::MutatorCtx::new_from_global(global);
+    eval_to_string_in(&ctx, source)
+}
+
+/// Like `eval_to_string`, on the heap of `ctx`
+#[cfg(test)]
+pub fn eval_to_string_in(ctx: &crate::thread::MutatorCtx, source: &str) -> String {
+    crate::let_slot!(ctx: scope);
+    let scope = core(ctx, scope);
+
+    crate::let_slot!(ctx: code);
+    let code = crate::parse::parse(source, ctx, code).unwrap();
+
+    crate::let_slot!(ctx: out);
+    let out = eval::rust_eval(ctx, out, code.value(), scope.value()).unwrap();
+    unsafe { out.value().unguard() }.to_string()
+}
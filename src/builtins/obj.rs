@@ -18,5 +18,5 @@ def_builtin!(objrest(ctx, out) [obj: objp] {
 });
 
 def_builtin!(obj(ctx, out) [first, rest] {
-    Ok(out.alloc_obj(ctx, Cons { first, rest }))
+    Ok(out.alloc_obj(ctx, Cons { first, rest })?)
 });
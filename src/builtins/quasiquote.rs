@@ -45,7 +45,7 @@ def_builtin!(eval_quasiquote(ctx, out) [scope, datum, level] {
                 let_slot!(ctx:rest);
                 let first = rust_eval_quasiquote(ctx, first, scope, cons.first, level)?;
                 let rest = rust_eval_quasiquote(ctx, rest, scope, cons.rest, level)?;
-                Ok(out.alloc_cons(ctx, Cons { first: first.value(), rest: rest.value() }))
+                Ok(out.alloc_cons(ctx, Cons { first: first.value(), rest: rest.value() })?)
             }
         },
         _ => Ok(out.root(&datum))
@@ -0,0 +1,178 @@
+use std::sync::mpsc;
+
+use crate::{
+    builtins::{eval::rust_apply, BuiltinError},
+    def_builtin, let_slot,
+    thread::{MutatorCtx, SendPtr, SpawnedThread, ThreadResult, EVAL_STACK_SIZE},
+    value::Value,
+};
+
+// Threads share the heap, so the new thread is handed the function and its
+// arguments rather than copies. `spawn` waits until it has rooted them, and
+// fails with `OutOfMemory` if the heap has no room for the new thread's
+// mutator. The new thread's mutator collects under stress as often as the
+// spawning one.
+def_builtin!(spawn(ctx, out) [func, &rest args] {
+    let global = ctx.global;
+    let max_eval_depth = ctx.max_eval_depth.get();
+    let stress_gc = ctx.alloc.stress_gc();
+    let func = SendPtr(unsafe { func.unguard() });
+    let args = SendPtr(unsafe { args.unguard() });
+    let (started_tx, started_rx) = mpsc::channel();
+    let (result_tx, result_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+
+    let handle = std::thread::Builder::new()
+        .stack_size(EVAL_STACK_SIZE)
+        .spawn(move || {
+            let ctx = match MutatorCtx::try_new_from_global(global) {
+                Ok(ctx) => ctx,
+                Err(err) => {
+                    started_tx.send(Err(err)).unwrap();
+                    return;
+                }
+            };
+            ctx.max_eval_depth.set(max_eval_depth);
+            ctx.alloc.set_stress_gc(stress_gc);
+            let_slot!(ctx: func_root, ctx: args_root);
+            let func = func_root.root_raw(func.0);
+            let args = args_root.root_raw(args.0);
+            started_tx.send(Ok(())).unwrap();
+
+            let_slot!(ctx: out);
+            let result = rust_apply(&ctx, out, func.value(), args.value());
+            let sent: ThreadResult = match &result {
+                Ok(root) => Ok(SendPtr(unsafe { root.packed() })),
+                Err(err) => Err(err.to_string()),
+            };
+            // the result stays rooted here until the joining thread has rooted it
+            if result_tx.send(sent).is_ok() {
+                ctx.alloc.park_while(|| done_rx.recv()).ok();
+            }
+        })
+        .map_err(|err| BuiltinError::ThreadFailed(err.to_string()))?;
+
+    let started = ctx.alloc
+        .park_while(|| started_rx.recv())
+        .map_err(|_| BuiltinError::ThreadFailed("thread exited before it started".into()))?;
+    if let Err(err) = started {
+        ctx.alloc.park_while(|| handle.join()).ok();
+        return Err(err.into());
+    }
+
+    let mut table = global.threads.lock().unwrap();
+    let id = table.next_id;
+    table.next_id += 1;
+    table.threads.insert(id, SpawnedThread { handle, result: result_rx, done: done_tx });
+    drop(table);
+
+    Ok(out.root(&Value::Integer(id as isize).pack()).prepend_obj(ctx, &ctx.common_symbols.thread)?)
+});
+
+// Waits for a thread started by `spawn` and returns its result, or fails with
+// the error it failed with. Each thread can be joined once.
+def_builtin!(join(ctx, out) [thread: objp] {
+    let id = match thread.unpack() {
+        Value::Object(obj) if obj.first == ctx.common_symbols.thread => match obj.rest.unpack() {
+            Value::Integer(id) => id as usize,
+            _ => return Err(BuiltinError::BadArgument("join: malformed thread".into())),
+        },
+        _ => return Err(BuiltinError::BadArgument(format!("join: {} is not a thread", unsafe { thread.unguard() }))),
+    };
+    let spawned = ctx.global.threads.lock().unwrap().threads.remove(&id)
+        .ok_or_else(|| BuiltinError::BadArgument("join: thread already joined".into()))?;
+
+    let result = match ctx.alloc.park_while(|| spawned.result.recv()) {
+        Ok(Ok(ptr)) => Ok(out.root_raw(ptr.0)),
+        Ok(Err(err)) => Err(BuiltinError::ThreadFailed(err)),
+        Err(_) => Err(BuiltinError::ThreadFailed("thread panicked".into())),
+    };
+    spawned.done.send(()).ok();
+    ctx.alloc.park_while(|| spawned.handle.join()).ok();
+    result
+});
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builtins::{core, eval::rust_eval, eval_to_string, BuiltinError},
+        let_slot,
+    };
+
+    #[test]
+    fn test_spawn_join() {
+        assert_eq!(
+            eval_to_string("(join (spawn (lambda (n) (+ n 1)) 41))"),
+            "42"
+        );
+    }
+
+    #[test]
+    fn test_parallel_lists() {
+        // each thread builds and sums its own list while the others collect
+        let out = eval_to_string(
+            "((lambda (build sum)
+                (map (lambda (thread) (join thread))
+                     (map (lambda (n) (spawn (lambda (n) (sum sum (build build n ()) 0)) n))
+                          (list 1000 2000 3000 4000))))
+              (lambda (self n acc) (if (= n 0) acc (self self (- n 1) (cons n acc))))
+              (lambda (self l acc) (if (consp l) (self self (rest l) (+ acc (first l))) acc)))",
+        );
+        assert_eq!(out, "(500500 2001000 4501500 8002000)");
+    }
+
+    #[test]
+    fn test_join_errors() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: scope);
+        let scope = core(&ctx, scope);
+
+        let_slot!(ctx: code);
+        let code = crate::parse::parse(
+            "((lambda (thread) (list (join thread) (join thread))) (spawn (lambda () 1)))",
+            &ctx,
+            code,
+        )
+        .unwrap();
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, code.value(), scope.value()) {
+            Err(err) => assert_eq!(err.to_string(), "join: thread already joined"),
+            Ok(_) => panic!("expected joining twice to fail"),
+        }
+
+        let code =
+            crate::parse::parse("(join (spawn (lambda () (car 1))))", &ctx, code.slot()).unwrap();
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, code.value(), scope.value()) {
+            Err(err) => assert_eq!(err.to_string(), "thread failed: undefined symbol car"),
+            Ok(_) => panic!("expected the thread to fail"),
+        }
+    }
+
+    #[test]
+    fn test_spawn_out_of_memory() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        ctx.alloc.set_stress_gc(None);
+
+        let_slot!(ctx: scope);
+        let scope = core(&ctx, scope);
+        let_slot!(ctx: code);
+        let code = crate::parse::parse("(spawn (lambda () 1))", &ctx, code).unwrap();
+        // no room for another block, so none for the new thread's first one
+        global
+            .alloc_state
+            .lock()
+            .unwrap()
+            .set_max_heap_size(Some(0));
+
+        let_slot!(ctx: out);
+        match rust_eval(&ctx, out, code.value(), scope.value()) {
+            Err(BuiltinError::OutOfMemory) => (),
+            _ => panic!("expected to run out of memory"),
+        }
+        assert!(global.threads.lock().unwrap().threads.is_empty());
+    }
+}
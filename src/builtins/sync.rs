@@ -0,0 +1,279 @@
+use std::ptr::NonNull;
+
+use crate::{
+    builtins::BuiltinError,
+    def_builtin, let_slot,
+    object::{PackedPtr, RawCons, UnpackedPtr},
+    thread::MutatorCtx,
+    value::{PackedValue, Value},
+};
+
+// Channels and mutexes are objects around a cons that, unlike every other
+// object, is written after it is allocated, always under the monitor of the
+// global state and through the write barrier. A channel is
+// `(channel queue last . closed)`, where `last` is the final cons of `queue`;
+// a mutex is `(mutex locked . value)`. Their contents are ordinary heap
+// values, so the collector traces them from whichever thread reaches the
+// object. Waiting parks the thread, so a builtin that waits roots the object
+// and finds its cons again afterwards, in case a collection moved it.
+
+/// The cons inside a channel or mutex object
+fn sync_state(
+    ctx: &MutatorCtx,
+    name: &str,
+    kind: &str,
+    value: PackedValue,
+) -> Result<NonNull<RawCons>, BuiltinError> {
+    let tag = match kind {
+        "channel" => &ctx.common_symbols.channel,
+        _ => &ctx.common_symbols.mutex,
+    };
+    match value.unpack() {
+        Value::Object(obj) if obj.first == *tag => match unsafe { obj.rest.unguard() }.unpack() {
+            UnpackedPtr::Cons(state) => Ok(state),
+            _ => Err(BuiltinError::BadArgument(format!(
+                "{}: malformed {}",
+                name, kind
+            ))),
+        },
+        _ => Err(BuiltinError::BadArgument(format!(
+            "{}: {} is not a {}",
+            name,
+            unsafe { value.unguard() },
+            kind
+        ))),
+    }
+}
+
+// read and written through raw pointers, since other threads write them too
+fn first(cons: NonNull<RawCons>) -> PackedPtr {
+    unsafe { (*cons.as_ptr()).first }
+}
+
+fn rest(cons: NonNull<RawCons>) -> PackedPtr {
+    unsafe { (*cons.as_ptr()).rest }
+}
+
+unsafe fn set_first(ctx: &MutatorCtx, cons: NonNull<RawCons>, value: PackedPtr) {
+    ctx.alloc
+        .write_barrier(PackedPtr::cons_ptr(cons), first(cons), value);
+    unsafe { (*cons.as_ptr()).first = value }
+}
+
+unsafe fn set_rest(ctx: &MutatorCtx, cons: NonNull<RawCons>, value: PackedPtr) {
+    ctx.alloc
+        .write_barrier(PackedPtr::cons_ptr(cons), rest(cons), value);
+    unsafe { (*cons.as_ptr()).rest = value }
+}
+
+fn as_cons(ptr: PackedPtr) -> Option<NonNull<RawCons>> {
+    match ptr.unpack() {
+        UnpackedPtr::Cons(cons) => Some(cons),
+        _ => None,
+    }
+}
+
+fn is_nil(ptr: PackedPtr) -> bool {
+    matches!(ptr.unpack(), UnpackedPtr::Nil)
+}
+
+// Takes the first value off the queue of a channel. Unsafe because the
+// monitor must be held.
+unsafe fn dequeue(ctx: &MutatorCtx, state: NonNull<RawCons>) -> Option<PackedPtr> {
+    let head = as_cons(first(state))?;
+    unsafe { set_first(ctx, state, rest(head)) };
+    if is_nil(rest(head)) {
+        unsafe { set_first(ctx, as_cons(rest(state)).unwrap(), PackedPtr::nil()) };
+    }
+    Some(first(head))
+}
+
+def_builtin!(make_channel(ctx, out) [] {
+    let_slot!(ctx: state);
+    let state = state.nil().singleton(ctx)?.prepend(ctx, &Value::Nil.pack())?;
+    Ok(out.root(&state.value()).prepend_obj(ctx, &ctx.common_symbols.channel)?)
+});
+
+def_builtin!(send(ctx, out) [channel, value] {
+    let state = sync_state(ctx, "send", "channel", channel)?;
+    // allocated first, since the monitor must not be held across allocations
+    let_slot!(ctx: cell);
+    let cell = cell.root(&value).singleton(ctx)?;
+
+    let monitor = &ctx.global.monitor;
+    let mut guard = monitor.lock();
+    let last = as_cons(rest(state)).unwrap();
+    if !is_nil(rest(last)) {
+        return Err(BuiltinError::BadArgument("send: channel closed".into()));
+    }
+    unsafe {
+        match as_cons(first(last)) {
+            Some(prev) => set_rest(ctx, prev, cell.packed()),
+            None => set_first(ctx, state, cell.packed()),
+        }
+        set_first(ctx, last, cell.packed());
+    }
+    monitor.notify(&mut guard);
+    Ok(out.root(&ctx.common_symbols.t))
+});
+
+// Waits for a value, or returns nil once the channel is closed and empty
+def_builtin!(recv(ctx, out) [channel] {
+    let_slot!(ctx: rooted);
+    let channel = rooted.root(&channel);
+    let monitor = &ctx.global.monitor;
+    let mut guard = monitor.lock();
+    loop {
+        let state = sync_state(ctx, "recv", "channel", channel.value())?;
+        if let Some(value) = unsafe { dequeue(ctx, state) } {
+            return Ok(out.root_raw(value));
+        }
+        if !is_nil(rest(as_cons(rest(state)).unwrap())) {
+            return Ok(out.nil());
+        }
+        guard = monitor.wait(ctx, guard);
+    }
+});
+
+// Returns a list of the next value if one is waiting, or nil otherwise
+def_builtin!(try_recv(ctx, out) [channel] {
+    let state = sync_state(ctx, "try-recv", "channel", channel)?;
+    let_slot!(ctx: item);
+    let guard = ctx.global.monitor.lock();
+    let item = match unsafe { dequeue(ctx, state) } {
+        Some(value) => item.root_raw(value),
+        None => return Ok(out.nil()),
+    };
+    drop(guard);
+    Ok(out.root(&item.value()).singleton(ctx)?)
+});
+
+def_builtin!(close(ctx, out) [channel] {
+    let state = sync_state(ctx, "close", "channel", channel)?;
+    let monitor = &ctx.global.monitor;
+    let mut guard = monitor.lock();
+    unsafe { set_rest(ctx, as_cons(rest(state)).unwrap(), ctx.common_symbols.t.unguard()) };
+    monitor.notify(&mut guard);
+    Ok(out.root(&ctx.common_symbols.t))
+});
+
+def_builtin!(make_mutex(ctx, out) [value] {
+    Ok(out.root(&value).prepend(ctx, &Value::Nil.pack())?.prepend_obj(ctx, &ctx.common_symbols.mutex)?)
+});
+
+// Waits until the mutex is unlocked, locks it and returns its value. It stays
+// locked until `unlock`, by any thread, even if the locking thread fails.
+def_builtin!(lock(ctx, out) [mutex] {
+    let_slot!(ctx: rooted);
+    let mutex = rooted.root(&mutex);
+    let mut state = sync_state(ctx, "lock", "mutex", mutex.value())?;
+    let monitor = &ctx.global.monitor;
+    let mut guard = monitor.lock();
+    while !is_nil(first(state)) {
+        guard = monitor.wait(ctx, guard);
+        state = sync_state(ctx, "lock", "mutex", mutex.value())?;
+    }
+    unsafe { set_first(ctx, state, ctx.common_symbols.t.unguard()) };
+    Ok(out.root_raw(rest(state)))
+});
+
+// Stores `value` in a locked mutex and unlocks it
+def_builtin!(unlock(ctx, out) [mutex, value] {
+    let state = sync_state(ctx, "unlock", "mutex", mutex)?;
+    let monitor = &ctx.global.monitor;
+    let mut guard = monitor.lock();
+    if is_nil(first(state)) {
+        return Err(BuiltinError::BadArgument("unlock: mutex is not locked".into()));
+    }
+    unsafe {
+        set_rest(ctx, state, value.unguard());
+        set_first(ctx, state, PackedPtr::nil());
+    }
+    monitor.notify(&mut guard);
+    Ok(out.root(&ctx.common_symbols.t))
+});
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        builtins::{core, eval::rust_eval, eval_to_string},
+        let_slot,
+    };
+
+    // arguments are evaluated last to first, so these read from the bottom up
+
+    #[test]
+    fn test_channel() {
+        assert_eq!(
+            eval_to_string(
+                "((lambda (ch)
+                    (list (recv ch) (recv ch) (close ch) (try-recv ch)
+                          (send ch 6) (send ch 5) (try-recv ch)))
+                  (make-channel))"
+            ),
+            "(() 6 t (5) t t ())"
+        );
+    }
+
+    #[test]
+    fn test_channel_between_threads() {
+        // the producer collects after every send, while the consumer waits
+        let out = eval_to_string(
+            "((lambda (ch produce drain)
+                ((lambda (producer) (list (join producer) (drain drain ch 0)))
+                 (spawn produce produce ch 1000)))
+              (make-channel)
+              (lambda (self ch n)
+                (if (= n 0)
+                    (close ch)
+                    (and (send ch (list n)) (unless (gc) (self self ch (- n 1))))))
+              (lambda (self ch acc)
+                ((lambda (v) (if v (self self ch (+ acc (first v))) acc)) (recv ch))))",
+        );
+        assert_eq!(out, "(t 500500)");
+    }
+
+    #[test]
+    fn test_mutex() {
+        let out = eval_to_string(
+            "((lambda (m bump)
+                (first (list (lock m)
+                             (map (lambda (thread) (join thread))
+                                  (map (lambda (n) (spawn bump bump m n))
+                                       (list 100 100 100 100))))))
+              (make-mutex 0)
+              (lambda (self m n)
+                (if (= n 0) 0 (and (unlock m (+ (lock m) 1)) (self self m (- n 1))))))",
+        );
+        assert_eq!(out, "400");
+    }
+
+    #[test]
+    fn test_sync_errors() {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: scope);
+        let scope = core(&ctx, scope);
+
+        for (source, error) in [
+            (
+                "((lambda (ch) (list (send ch 1) (close ch))) (make-channel))",
+                "send: channel closed",
+            ),
+            ("(unlock (make-mutex 1) 2)", "unlock: mutex is not locked"),
+            (
+                "(recv (make-mutex 1))",
+                "recv: <OBJECT mutex> is not a channel",
+            ),
+        ] {
+            let_slot!(ctx: code);
+            let code = crate::parse::parse(source, &ctx, code).unwrap();
+            let_slot!(ctx: out);
+            match rust_eval(&ctx, out, code.value(), scope.value()) {
+                Err(err) => assert_eq!(err.to_string(), error),
+                Ok(_) => panic!("expected {} to fail", source),
+            }
+        }
+    }
+}
@@ -19,7 +19,16 @@ macro_rules! generate_predicate {
     };
 }
 
-generate_predicate!(consp, nilp, listp, proper_list_p, objp);
+generate_predicate!(
+    consp,
+    nilp,
+    listp,
+    proper_list_p,
+    objp,
+    stringp,
+    floatp,
+    numberp
+);
 
 pub mod rust {
     use crate::{
@@ -70,6 +79,21 @@ pub mod rust {
         }
     }
 
+    pub fn stringp(arg: PackedValue) -> bool {
+        matches!(arg.unpack(), Value::String(_))
+    }
+
+    pub fn floatp(arg: PackedValue) -> bool {
+        matches!(arg.unpack(), Value::Float(_))
+    }
+
+    pub fn numberp(arg: PackedValue) -> bool {
+        matches!(
+            arg.unpack(),
+            Value::Integer(_) | Value::Bigint(_) | Value::Float(_)
+        )
+    }
+
     pub fn tagp(tag: PackedValue, arg: PackedValue) -> bool {
         match arg.unpack() {
             Value::Object(cons) => cons.first == tag,
@@ -89,39 +113,38 @@ mod test {
 
     #[test]
     fn test_nil_types() {
-        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
-        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        let ctx = crate::builtins::stress_ctx(1);
 
         let_slot!(ctx: args);
-        let args = args.nil().quote(&ctx).singleton(&ctx);
+        let args = args.nil().singleton(&ctx).unwrap();
 
         test_types(&ctx, args, [true, false, true, true]);
     }
 
     #[test]
     fn test_improper_cons_types() {
-        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
-        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        let ctx = crate::builtins::stress_ctx(1);
 
         let_slot!(ctx: args);
         let args = args
             .alloc_cons(&ctx, Cons::new(Value::Integer(2), Value::Integer(3)))
-            .quote(&ctx)
-            .singleton(&ctx);
+            .unwrap()
+            .singleton(&ctx)
+            .unwrap();
 
         test_types(&ctx, args, [false, true, true, false]);
     }
 
     #[test]
     fn test_proper_cons_types() {
-        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
-        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+        let ctx = crate::builtins::stress_ctx(1);
 
         let_slot!(ctx: args);
         let args = args
             .alloc_cons(&ctx, Cons::new(Value::Integer(2), Value::Nil))
-            .quote(&ctx)
-            .singleton(&ctx);
+            .unwrap()
+            .singleton(&ctx)
+            .unwrap();
 
         test_types(&ctx, args, [false, true, true, true]);
     }
@@ -1,4 +1,5 @@
 use crate::{
+    number::Number,
     object::TagType,
     root::Gc,
     value::{Cons, PackedValue, Value},
@@ -24,3 +25,9 @@ pub fn unpack_int<'guard>(arg: PackedValue<'guard>) -> Result<isize, TagType> {
         _ => Err(unsafe { arg.unguard().tag_type() }),
     }
 }
+
+/// Like `unpack_int`, but also takes bigints and floats, which `|int` would
+/// reject, so that the arithmetic builtins accept the whole numeric tower
+pub fn unpack_number<'guard>(arg: PackedValue<'guard>) -> Result<Number, TagType> {
+    Number::from_value(arg).ok_or_else(|| unsafe { arg.unguard().tag_type() })
+}
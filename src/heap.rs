@@ -1,8 +1,17 @@
 use crate::alloc::AllocError;
-use std::ptr::NonNull;
+use std::{mem::size_of, ptr::NonNull};
 
 pub trait LAlloc {
-    fn alloc<T, R, F: FnOnce(NonNull<T>) -> R>(&self, transformer: F) -> Result<R, AllocError>;
+    /// Allocates `size` bytes for a `T` with trailing data, such as a string header and its bytes
+    fn alloc_sized<T, R, F: FnOnce(NonNull<T>) -> R>(
+        &self,
+        size: usize,
+        transformer: F,
+    ) -> Result<R, AllocError>;
+
+    fn alloc<T, R, F: FnOnce(NonNull<T>) -> R>(&self, transformer: F) -> Result<R, AllocError> {
+        self.alloc_sized(size_of::<T>(), transformer)
+    }
 
     fn object<T, R, F: FnOnce(NonNull<T>) -> R>(
         &self,
@@ -16,6 +25,7 @@ pub trait LAlloc {
     }
 }
 
+#[allow(dead_code)]
 pub fn id<T>(ptr: NonNull<T>) -> NonNull<T> {
     ptr
 }
@@ -69,6 +69,7 @@ macro_rules! def_builtin {
 
             #[allow(unused_mut)]
             #[allow(unused_variables)]
+            #[allow(clippy::extra_unused_lifetimes)]
             pub fn [<rust_ $name>]<'o, 'a>($($ctx: &'o crate::thread::MutatorCtx,)? $(mut $out: crate::root::Slot<'o>,)? $($(
                 mut $arg_name: crate::value::PackedValue<'a>,
             )* $(mut $rest: crate::value::PackedValue<'a>,)?)?) -> crate::builtins::BuiltinResult<'o> {
@@ -1,90 +1,311 @@
 use std::{
     env, fs,
     io::{stdin, stdout, Write},
+    time::Duration,
 };
 
-use root::{Root, Slot};
+use root::Root;
 use value::PackedValue;
 
-use crate::builtins::{core, eval::rust_eval};
+use crate::builtins::{core, eval::rust_eval, unpack::unpack_cons};
 #[macro_use]
 extern crate pest_derive;
 
 mod alloc;
 mod arena;
+mod bigint;
 mod builtins;
 mod heap;
 mod linked_list;
 mod macros;
+mod number;
 mod object;
 mod parse;
 mod print;
 mod root;
+mod safepoint;
 mod sorted_vec;
+mod source;
 mod thread;
 mod util;
 mod value;
 
 fn main() {
+    // a larger stack than the main thread gets, for deep recursion
+    std::thread::Builder::new()
+        .stack_size(thread::EVAL_STACK_SIZE)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn run() {
     let global = Box::leak(Box::new(thread::GlobalState::new()));
     let ctx = thread::MutatorCtx::new_from_global(global);
+    ctx.max_eval_depth
+        .set(thread::max_eval_depth_for(thread::EVAL_STACK_SIZE));
 
     let_slot!(ctx: scope);
     let mut scope = core(&ctx, scope);
 
     global.alloc_state.lock().unwrap().gc();
 
-    for path in env::args().skip(1) {
-        let_slot!(ctx: eval_out);
+    let mut interactive = false;
+    let mut print_gc_stats = false;
+    let mut paths = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" => interactive = true,
+            "--gc-stats" => print_gc_stats = true,
+            "--verify-heap" => global.alloc_state.lock().unwrap().set_verify(true),
+            "--stress-gc" => {
+                let interval = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .expect("--stress-gc takes a positive number of allocations");
+                ctx.alloc.set_stress_gc(Some(interval));
+            }
+            "--max-heap" => {
+                let megabytes: usize = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--max-heap takes a size in megabytes");
+                global
+                    .alloc_state
+                    .lock()
+                    .unwrap()
+                    .set_max_heap_size(Some(megabytes * 1024 * 1024));
+            }
+            "--full-gc-interval" => {
+                let interval = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .expect("--full-gc-interval takes a positive number of collections");
+                global
+                    .alloc_state
+                    .lock()
+                    .unwrap()
+                    .set_full_collection_interval(interval);
+            }
+            "--incremental" => {
+                let budget = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .expect("--incremental takes a positive number of objects per increment");
+                global
+                    .alloc_state
+                    .lock()
+                    .unwrap()
+                    .set_incremental(Some(budget));
+            }
+            "--increment-pause" => {
+                let millis = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--increment-pause takes a number of milliseconds");
+                global
+                    .alloc_state
+                    .lock()
+                    .unwrap()
+                    .set_increment_pause(Some(Duration::from_millis(millis)));
+            }
+            "--mark-threads" => {
+                let threads = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .expect("--mark-threads takes a positive number of threads");
+                global.alloc_state.lock().unwrap().set_mark_threads(threads);
+            }
+            "--free-blocks" => {
+                let blocks = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--free-blocks takes a number of blocks");
+                global
+                    .alloc_state
+                    .lock()
+                    .unwrap()
+                    .set_free_block_budget(blocks);
+            }
+            _ => paths.push(arg),
+        }
+    }
 
+    for path in paths.iter() {
         let source = fs::read_to_string(path).expect("cannot read file");
-        if let Some(out) = eval_string(&ctx, scope.value(), eval_out, source.as_str()) {
-            scope = scope.slot().root(&out.value());
-        }
+        scope = eval_string(&ctx, scope, path, source.as_str());
     }
 
-    // unsafe { println!("Scope: {}", scope.value().unguard()) };
+    if paths.is_empty() || interactive {
+        repl(&ctx, scope);
+    }
 
-    return;
+    if print_gc_stats {
+        eprintln!("{:#?}", global.gc_stats());
+    }
+}
 
+fn repl(ctx: &thread::MutatorCtx, mut scope: Root) {
     let stdin = stdin();
     let mut stdout = stdout();
+    let mut buffer = String::new();
     loop {
-        let mut buffer = String::new();
-        print!(">>> ");
+        print!("{}", if buffer.is_empty() { ">>> " } else { "... " });
         stdout.flush().unwrap();
-        stdin.read_line(&mut buffer).unwrap();
+        match stdin.read_line(&mut buffer) {
+            Ok(0) => {
+                // EOF
+                println!();
+                return;
+            }
+            Ok(_) => (),
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        }
 
-        let_slot!(ctx: eval_out);
-        let out = eval_string(&ctx, scope.value(), eval_out, buffer.as_str());
+        if buffer.trim().is_empty() {
+            buffer.clear();
+            continue;
+        }
+        if !is_balanced(buffer.as_str()) {
+            continue;
+        }
+
+        scope = eval_string(ctx, scope, "<repl>", buffer.as_str());
+        buffer.clear();
     }
 }
 
-fn eval_string<'o, 'a>(
-    ctx: &'o thread::MutatorCtx,
-    scope: PackedValue<'a>,
-    out: Slot<'o>,
-    str: &str,
-) -> Option<Root<'o>> {
-    let_slot!(ctx: parse_out);
-    let res = parse::parse(str, &ctx, parse_out);
-    match res {
-        Ok(code) => {
-            let res = rust_eval(&ctx, out, code.value(), scope);
-            match res {
-                Ok(eval_out) => unsafe {
-                    println!("{}", eval_out.value().unguard());
-                    return Some(eval_out);
-                },
-                Err(err) => {
-                    println!("Eval error: {:?}", err);
-                    return None;
+/// Whether `input` closes every paren it opens, ignoring parens inside strings and comments
+fn is_balanced(input: &str) -> bool {
+    let mut depth = 0isize;
+    let mut in_string = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' => {
+                chars.find(|&c| c == '\n');
+            }
+            '#' if chars.peek() == Some(&'|') => {
+                chars.next();
+                let mut nesting = 1;
+                while nesting > 0 {
+                    match (chars.next(), chars.peek()) {
+                        (None, _) => return false,
+                        (Some('|'), Some('#')) => {
+                            chars.next();
+                            nesting -= 1;
+                        }
+                        (Some('#'), Some('|')) => {
+                            chars.next();
+                            nesting += 1;
+                        }
+                        _ => (),
+                    }
                 }
             }
+            _ => (),
         }
+    }
+    !in_string && depth <= 0
+}
+
+/// Whether `value` is `scope` with bindings prepended, as `bind` and `bind*` return.
+/// Any other result is printed but leaves the scope alone.
+fn extends_scope(value: PackedValue, scope: PackedValue) -> bool {
+    let mut rest = value;
+    loop {
+        if rest == scope {
+            return true;
+        }
+        match unpack_cons(rest) {
+            Ok(cons) => rest = cons.rest,
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Evaluates the top-level forms of `str`, read from `file`, in order, stopping
+/// at the first error. Returns `scope` extended by the bindings the forms introduced.
+fn eval_string<'s>(
+    ctx: &thread::MutatorCtx,
+    mut scope: Root<'s>,
+    file: &str,
+    str: &str,
+) -> Root<'s> {
+    let_slot!(ctx: parse_out);
+    let mut forms = match parse::parse_program(str, file, ctx, parse_out) {
+        Ok(forms) => forms,
         Err(err) => {
             println!("{}", err);
-            return None;
+            return scope;
+        }
+    };
+
+    while let Ok(cons) = unpack_cons(forms.value()) {
+        let rest = unsafe { cons.rest.unguard() };
+        let_slot!(ctx: eval_out);
+        match rust_eval(ctx, eval_out, cons.first, scope.value()) {
+            Ok(out) => {
+                println!("{}", unsafe { out.value().unguard() });
+                if extends_scope(out.value(), scope.value()) {
+                    scope = scope.slot().root(&out.value());
+                }
+            }
+            Err(err) => {
+                println!("{}", err);
+                break;
+            }
         }
+        forms.set_raw(rest);
+
+        // between top-level forms every reference this thread holds into the
+        // heap is rooted, but threads spawned and not yet joined may still be
+        // running Lisp with unrooted pointers. Only a full collection finds
+        // fragmented blocks, and the driver leaves when to run one to the
+        // collector.
+        if ctx.alloc.fragmented() && ctx.global.threads.lock().unwrap().threads.is_empty() {
+            unsafe { ctx.alloc.defragment() };
+        }
+    }
+    scope
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_balanced;
+
+    #[test]
+    fn test_is_balanced() {
+        assert!(is_balanced("(+ 1 2)"));
+        assert!(!is_balanced("(bind* (x . 1)\n"));
+        assert!(is_balanced("(bind* (x . 1)\n  (y . 2))"));
+        assert!(!is_balanced("(print \"(\""));
+        assert!(is_balanced("(print \"\\\"(\")"));
+        assert!(is_balanced("x"));
+        assert!(!is_balanced("(+ 1 ; 2)\n"));
+        assert!(is_balanced("(+ 1 #| ( |# 2)"));
+        assert!(!is_balanced("#| #| |# ("));
     }
 }
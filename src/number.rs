@@ -0,0 +1,86 @@
+use std::cmp::Ordering;
+
+use crate::{
+    bigint::BigInt,
+    object::{FIXNUM_MAX, FIXNUM_MIN},
+    value::{PackedValue, Value},
+};
+
+/// A numeric value lifted out of the heap so mixed arithmetic can be promoted.
+/// `Integer` always fits in a smallint and `Big` never does.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Number {
+    Integer(isize),
+    Big(BigInt),
+    Float(f64),
+}
+
+/// Both operands of a binary operation, converted to their common type
+pub enum Promoted {
+    Integers(isize, isize),
+    Bigs(BigInt, BigInt),
+    Floats(f64, f64),
+}
+
+impl Number {
+    pub fn from_value(value: PackedValue) -> Option<Self> {
+        match value.unpack() {
+            Value::Integer(n) => Some(Number::Integer(n)),
+            Value::Bigint(n) => Some(Number::Big(n.to_bigint())),
+            Value::Float(n) => Some(Number::Float(n.value)),
+            _ => None,
+        }
+    }
+
+    /// Wide enough for the sum or product of two smallints
+    pub fn from_i128(n: i128) -> Self {
+        if (FIXNUM_MIN as i128..=FIXNUM_MAX as i128).contains(&n) {
+            Number::Integer(n as isize)
+        } else {
+            Number::Big(BigInt::from_i128(n))
+        }
+    }
+
+    /// Demotes bigints that fit back into a smallint
+    pub fn from_bigint(n: BigInt) -> Self {
+        match n.to_i128() {
+            Some(small) => Self::from_i128(small),
+            None => Number::Big(n),
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Integer(n) => *n as f64,
+            Number::Big(n) => n.to_f64(),
+            Number::Float(n) => *n,
+        }
+    }
+
+    pub fn to_bigint(&self) -> BigInt {
+        match self {
+            Number::Integer(n) => BigInt::from_i128(*n as i128),
+            Number::Big(n) => n.clone(),
+            Number::Float(n) => BigInt::from_i128(*n as i128),
+        }
+    }
+
+    pub fn promote(self, other: Number) -> Promoted {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => Promoted::Integers(a, b),
+            (a @ Number::Float(_), b) | (a, b @ Number::Float(_)) => {
+                Promoted::Floats(a.to_f64(), b.to_f64())
+            }
+            (a, b) => Promoted::Bigs(a.to_bigint(), b.to_bigint()),
+        }
+    }
+
+    /// Compares by numeric value across types; `None` if either side is NaN
+    pub fn numeric_cmp(&self, other: &Number) -> Option<Ordering> {
+        match self.clone().promote(other.clone()) {
+            Promoted::Integers(a, b) => Some(a.cmp(&b)),
+            Promoted::Bigs(a, b) => Some(a.cmp(&b)),
+            Promoted::Floats(a, b) => a.partial_cmp(&b),
+        }
+    }
+}
@@ -7,10 +7,14 @@ use std::{
     slice, string,
 };
 
-use crate::{builtins::BuiltinFunction, util::construct_non_null};
+use crate::{bigint::BigInt, builtins::BuiltinFunction, util::construct_non_null};
 
 pub const OBJECT_ALIGNMENT: usize = 8;
 
+/// Range of integers that fit in a 62-bit smallint; anything else is a bigint
+pub const FIXNUM_MIN: isize = -(1 << 61);
+pub const FIXNUM_MAX: isize = (1 << 61) - 1;
+
 struct GuardPtr<'a, T> {
     ptr: NonNull<T>,
     phantom: PhantomData<&'a T>,
@@ -22,6 +26,8 @@ pub union PackedPtr {
     integer: isize,
     cons: NonNull<RawCons>,
     lstr: NonNull<LString>,
+    string: NonNull<RawString>,
+    boxed: NonNull<BoxKind>,
     fun: BuiltinFunction,
 }
 
@@ -50,10 +56,27 @@ impl PackedPtr {
         unsafe { PackedPtr { lstr: ptr }.add_tag(TagType::Symbol as usize) }
     }
 
+    pub fn str_ptr(ptr: NonNull<RawString>) -> Self {
+        unsafe { PackedPtr { string: ptr }.add_tag(TagType::String as usize) }
+    }
+
+    pub fn float_ptr(ptr: NonNull<RawFloat>) -> Self {
+        unsafe { PackedPtr { boxed: ptr.cast() }.add_tag(TagType::Boxed as usize) }
+    }
+
+    pub fn bigint_ptr(ptr: NonNull<RawBigint>) -> Self {
+        unsafe { PackedPtr { boxed: ptr.cast() }.add_tag(TagType::Boxed as usize) }
+    }
+
     pub fn fun_ptr(ptr: BuiltinFunction) -> Self {
         unsafe { PackedPtr { fun: ptr }.add_tag(TagType::Function as usize) }
     }
 
+    /// The tagged bits, for checking a value without interpreting it
+    pub fn raw(&self) -> usize {
+        unsafe { self.tag }
+    }
+
     unsafe fn add_tag(&self, tag: usize) -> Self {
         PackedPtr {
             tag: (self.tag & !7) | tag,
@@ -75,6 +98,14 @@ impl PackedPtr {
         PackedPtr { tag: self.tag & !7 }.lstr
     }
 
+    unsafe fn get_str_ptr(&self) -> NonNull<RawString> {
+        PackedPtr { tag: self.tag & !7 }.string
+    }
+
+    unsafe fn get_box_ptr(&self) -> NonNull<BoxKind> {
+        PackedPtr { tag: self.tag & !7 }.boxed
+    }
+
     unsafe fn get_fun_ptr(&self) -> BuiltinFunction {
         PackedPtr { tag: self.tag & !7 }.fun
     }
@@ -88,6 +119,8 @@ impl PackedPtr {
             t if t == 0 as usize => TagType::Nil,
             t if (t & 7) == TagType::Symbol as usize => TagType::Symbol,
             t if (t & 7) == TagType::Function as usize => TagType::Function,
+            t if (t & 7) == TagType::String as usize => TagType::String,
+            t if (t & 7) == TagType::Boxed as usize => TagType::Boxed,
             _ => panic!("Heap corrupted"),
         }
     }
@@ -101,34 +134,56 @@ impl PackedPtr {
                 TagType::Nil => UnpackedPtr::Nil,
                 TagType::Symbol => UnpackedPtr::Symbol(self.get_sym_ptr()),
                 TagType::Function => UnpackedPtr::Function(self.get_fun_ptr()),
+                TagType::String => UnpackedPtr::String(self.get_str_ptr()),
+                TagType::Boxed => {
+                    let ptr = self.get_box_ptr();
+                    match *ptr.as_ref() {
+                        BoxKind::Float => UnpackedPtr::Float(ptr.cast()),
+                        BoxKind::Bigint => UnpackedPtr::Bigint(ptr.cast()),
+                    }
+                }
                 _ => panic!("Heap corrupted"),
             }
         }
     }
 
-    pub fn heap_ptrs(&self) -> Vec<(NonNull<u8>, usize)> {
+    /// The heap allocation behind this value and its size, if it has one
+    pub fn heap_ptr(&self) -> Option<(NonNull<u8>, usize)> {
         use crate::object::UnpackedPtr::*;
         unsafe {
             match self.unpack() {
-                Cons(ptr) | Object(ptr) => vec![(
+                Cons(ptr) | Object(ptr) => Some((
                     NonNull::new_unchecked(ptr.as_ptr() as *mut u8),
                     size_of::<crate::object::RawCons>(),
-                )],
-                _ => vec![],
+                )),
+                String(ptr) => Some((
+                    NonNull::new_unchecked(ptr.as_ptr() as *mut u8),
+                    RawString::alloc_size(ptr.as_ref().len),
+                )),
+                Float(ptr) => Some((
+                    NonNull::new_unchecked(ptr.as_ptr() as *mut u8),
+                    size_of::<RawFloat>(),
+                )),
+                Bigint(ptr) => Some((
+                    NonNull::new_unchecked(ptr.as_ptr() as *mut u8),
+                    RawBigint::alloc_size(ptr.as_ref().len),
+                )),
+                _ => None,
             }
         }
     }
 
-    pub fn obj_ptrs(&self) -> Vec<PackedPtr> {
+    /// The values this one refers to, if it has any
+    pub fn obj_ptrs(&self) -> Option<(PackedPtr, PackedPtr)> {
         use crate::object::UnpackedPtr::*;
         unsafe {
             match self.unpack() {
                 Cons(ptr) | Object(ptr) => {
                     let cons = *ptr.as_ptr();
 
-                    vec![cons.first, cons.rest]
+                    Some((cons.first, cons.rest))
                 }
-                _ => vec![],
+                _ => None,
             }
         }
     }
@@ -147,11 +202,13 @@ impl PartialEq for PackedPtr {
 }
 
 /**
- * xx00 - smallint (62-bit)
+ * xx11 - smallint (62-bit)
+ * x000 - symbol
  * x001 - cons
- * x010 - string
- * x011 - symbol
- * x101 - function
+ * x010 - function
+ * x100 - object
+ * x101 - string
+ * x110 - boxed (kind given by a BoxKind header)
  * vector
  * bigint
  * closure
@@ -164,11 +221,12 @@ pub enum TagType {
     Function = 0b010,
     Integer = 0b011,
     // Vector,
-    // Bigint,
     // Closure,
     // Map,
     // (Integer = 0b111)
     Object = 0b100,
+    String = 0b101,
+    Boxed = 0b110,
     Nil,
 }
 
@@ -180,6 +238,9 @@ pub enum UnpackedPtr {
     Nil,
     Symbol(NonNull<LString>),
     Function(BuiltinFunction),
+    String(NonNull<RawString>),
+    Float(NonNull<RawFloat>),
+    Bigint(NonNull<RawBigint>),
 }
 
 impl UnpackedPtr {
@@ -191,6 +252,9 @@ impl UnpackedPtr {
             UnpackedPtr::Nil => PackedPtr::nil(),
             UnpackedPtr::Symbol(ptr) => PackedPtr::sym_ptr(ptr),
             UnpackedPtr::Function(ptr) => PackedPtr::fun_ptr(ptr),
+            UnpackedPtr::String(ptr) => PackedPtr::str_ptr(ptr),
+            UnpackedPtr::Float(ptr) => PackedPtr::float_ptr(ptr),
+            UnpackedPtr::Bigint(ptr) => PackedPtr::bigint_ptr(ptr),
         }
     }
 }
@@ -202,6 +266,143 @@ pub struct RawCons {
     pub rest: PackedPtr,
 }
 
+/// Left in `first` of a cons the collector has moved. Builtins are never
+/// null, so no live cons can hold it.
+const FORWARDED: PackedPtr = PackedPtr {
+    tag: TagType::Function as usize,
+};
+
+impl RawCons {
+    /// Overwrites a cons that was copied to `to` with a forwarding pointer
+    pub fn forward(&mut self, to: NonNull<RawCons>) {
+        self.first = FORWARDED;
+        self.rest = PackedPtr::cons_ptr(to);
+    }
+
+    pub fn forwarded(&self) -> Option<NonNull<RawCons>> {
+        if self.first == FORWARDED {
+            Some(unsafe { self.rest.get_cons_ptr() })
+        } else {
+            None
+        }
+    }
+}
+
+/// First word of every object behind a `TagType::Boxed` pointer
+#[repr(usize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BoxKind {
+    Float,
+    Bigint,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RawFloat {
+    kind: BoxKind,
+    pub value: f64,
+}
+
+impl RawFloat {
+    pub fn new(value: f64) -> Self {
+        RawFloat {
+            kind: BoxKind::Float,
+            value,
+        }
+    }
+}
+
+// Bitwise, so that a float is always equal to itself, NaN included
+impl PartialEq for RawFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.value.to_bits() == other.value.to_bits()
+    }
+}
+
+impl Eq for RawFloat {}
+
+/// Header of a bigint outside the smallint range; the magnitude's
+/// little-endian limbs follow it inline.
+#[repr(C)]
+pub struct RawBigint {
+    kind: BoxKind,
+    negative: bool,
+    pub len: usize,
+    limbs: [u32; 0],
+}
+
+impl RawBigint {
+    pub fn alloc_size(len: usize) -> usize {
+        size_of::<RawBigint>() + len * size_of::<u32>()
+    }
+
+    /// Writes a bigint into a fresh allocation of at least
+    /// `alloc_size(n.magnitude().len())` bytes.
+    pub unsafe fn init(ptr: NonNull<RawBigint>, n: &BigInt) {
+        let raw = ptr.as_ptr();
+        (*raw).kind = BoxKind::Bigint;
+        (*raw).negative = n.negative();
+        (*raw).len = n.magnitude().len();
+        std::ptr::copy_nonoverlapping(
+            n.magnitude().as_ptr(),
+            (*raw).limbs.as_mut_ptr(),
+            n.magnitude().len(),
+        );
+    }
+
+    pub fn limbs(&self) -> &[u32] {
+        unsafe { slice::from_raw_parts(self.limbs.as_ptr(), self.len) }
+    }
+
+    pub fn to_bigint(&self) -> BigInt {
+        BigInt::from_parts(self.negative, self.limbs().to_vec())
+    }
+}
+
+impl PartialEq for RawBigint {
+    fn eq(&self, other: &Self) -> bool {
+        self.negative == other.negative && self.limbs() == other.limbs()
+    }
+}
+
+impl Eq for RawBigint {}
+
+/// Header of a heap-allocated string; the UTF-8 bytes follow it inline.
+#[repr(C)]
+pub struct RawString {
+    pub len: usize,
+    bytes: [u8; 0],
+}
+
+impl RawString {
+    pub fn alloc_size(len: usize) -> usize {
+        size_of::<RawString>() + len
+    }
+
+    /// Writes a string header and its contents into a fresh allocation of
+    /// at least `alloc_size(str.len())` bytes.
+    pub unsafe fn init(ptr: NonNull<RawString>, str: &str) {
+        let raw = ptr.as_ptr();
+        (*raw).len = str.len();
+        std::ptr::copy_nonoverlapping(str.as_ptr(), (*raw).bytes.as_mut_ptr(), str.len());
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe {
+            let slice = slice::from_raw_parts(self.bytes.as_ptr(), self.len);
+            std::str::from_utf8_unchecked(slice)
+        }
+    }
+}
+
+impl PartialEq for RawString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for RawString {}
+
 #[repr(align(4))]
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct LString {
@@ -209,15 +410,6 @@ pub struct LString {
     pub len: usize,
 }
 
-impl LString {
-    // Unsafe because this leaks memory
-    pub unsafe fn leak_str(name: String) -> Self {
-        let len = name.len();
-        let start = Box::leak(name.into_boxed_str()).as_ptr();
-        LString { start, len }
-    }
-}
-
 impl ToString for LString {
     fn to_string(&self) -> String {
         let slice = unsafe { slice::from_raw_parts(self.start, self.len) };
@@ -1,94 +1,158 @@
 extern crate pest;
 
-use pest::iterators::Pair;
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
+use std::fmt::Display;
+use std::str::Chars;
+use std::sync::Arc;
 
+use crate::alloc::AllocError;
+use crate::bigint::BigInt;
 use crate::heap::{id, LAlloc};
 use crate::let_slot;
+use crate::number::Number;
 use crate::object::{PackedPtr, RawCons};
-use crate::root::{Root, Slot};
+use crate::root::{AllocResult, Root, Slot};
+use crate::source::SourcePos;
 use crate::thread::MutatorCtx;
-use crate::value::{Cons, Value};
+use crate::value::Value;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 struct LParser;
 
-pub fn parse<'r>(
-    str: &str,
-    dest: &MutatorCtx,
-    out: Slot<'r>,
-) -> Result<Root<'r>, pest::error::Error<Rule>> {
-    let res = LParser::parse(Rule::top_level, str);
-    if let Err(err) = res {
-        return Err(err);
+#[derive(Debug)]
+pub enum ParseError {
+    Syntax(Box<pest::error::Error<Rule>>),
+    /// The heap had no room left for the data read
+    OutOfMemory,
+}
+
+impl From<pest::error::Error<Rule>> for ParseError {
+    fn from(err: pest::error::Error<Rule>) -> Self {
+        ParseError::Syntax(Box::new(err))
     }
-    match res.unwrap().next() {
-        Some(pair) => Ok(sexp_to_object(pair, dest, out)),
+}
+
+impl From<AllocError> for ParseError {
+    fn from(_: AllocError) -> Self {
+        ParseError::OutOfMemory
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Syntax(err) => write!(f, "{}", err),
+            ParseError::OutOfMemory => write!(f, "out of memory"),
+        }
+    }
+}
+
+/// Reads exactly one datum, as `lisp_read!` does
+#[allow(dead_code)]
+pub fn parse<'r>(str: &str, dest: &MutatorCtx, out: Slot<'r>) -> Result<Root<'r>, ParseError> {
+    let res = LParser::parse(Rule::top_level, str)?;
+    match data(res).next() {
+        Some(pair) => Ok(sexp_to_object(pair, dest, None, out)?),
         None => Ok(out.nil()),
     }
 }
 
-fn sexp_to_object<'r>(pair: Pair<Rule>, ctx: &MutatorCtx, out: Slot<'r>) -> Root<'r> {
+/// Reads every top-level form in `str` into a proper list, in order, recording
+/// where in `file` each list began
+pub fn parse_program<'r>(
+    str: &str,
+    file: &str,
+    ctx: &MutatorCtx,
+    out: Slot<'r>,
+) -> Result<Root<'r>, ParseError> {
+    let res = LParser::parse(Rule::program, str).map_err(|err| err.with_path(file))?;
+    let forms: Vec<_> = data(res).collect();
+    let file = Arc::from(file);
+
+    let_slot!(ctx: item);
+    let mut item = item;
+    let mut out = out.nil();
+    for pair in forms.into_iter().rev() {
+        let entry = sexp_to_object(pair, ctx, Some(&file), item)?;
+        out = out.prepend(ctx, &entry.value())?;
+        item = entry.slot();
+    }
+    Ok(out)
+}
+
+/// Drops the `#;` comments and end of input marker around the data of a rule
+fn data<'i>(pairs: Pairs<'i, Rule>) -> impl DoubleEndedIterator<Item = Pair<'i, Rule>> {
+    pairs.filter(|pair| !matches!(pair.as_rule(), Rule::datum_comment | Rule::EOI))
+}
+
+fn sexp_to_object<'r>(
+    pair: Pair<Rule>,
+    ctx: &MutatorCtx,
+    file: Option<&Arc<str>>,
+    out: Slot<'r>,
+) -> AllocResult<'r> {
     let rule = pair.as_rule();
-    match rule {
+    Ok(match rule {
         Rule::nil_term_list | Rule::custom_term_list => {
-            let mut iter = pair.into_inner().rev();
+            let (line, column) = pair.as_span().start_pos().line_col();
+            let mut iter = data(pair.into_inner()).rev();
             let out = if rule == Rule::nil_term_list {
                 out.nil()
             } else {
-                sexp_to_object(iter.next().unwrap(), ctx, out)
+                sexp_to_object(iter.next().unwrap(), ctx, file, out)?
             };
 
             let_slot!(ctx: item);
             let mut item = item;
             let mut out = out;
             for inner_pair in iter {
-                let entry = sexp_to_object(inner_pair, ctx, item);
-                out = out.prepend(ctx, &entry.value());
+                let entry = sexp_to_object(inner_pair, ctx, file, item)?;
+                out = out.prepend(ctx, &entry.value())?;
                 item = entry.slot();
             }
+
+            if let (Some(file), Value::Cons(_)) = (file, out.value().unpack()) {
+                let pos = SourcePos {
+                    file: file.clone(),
+                    line,
+                    column,
+                };
+                ctx.alloc.record_source(unsafe { out.packed() }, pos);
+            }
             out
         }
-        Rule::binary => out.root(
-            &Value::Integer(
-                isize::from_str_radix(pair.as_str().trim_start_matches("0b"), 2).unwrap(),
-            )
-            .pack(),
-        ),
-        Rule::octal => out.root(
-            &Value::Integer(
-                isize::from_str_radix(pair.as_str().trim_start_matches("0"), 8).unwrap(),
-            )
-            .pack(),
-        ),
-        Rule::decimal => out.root(&Value::Integer(pair.as_str().parse().unwrap()).pack()),
-        Rule::hexadecimal => out.root(
-            &Value::Integer(
-                isize::from_str_radix(pair.as_str().trim_start_matches("0x"), 16).unwrap(),
-            )
-            .pack(),
-        ),
+        Rule::binary => out.number(ctx, parse_integer(pair.as_str(), "0b", 2))?,
+        Rule::octal => out.number(ctx, parse_integer(pair.as_str(), "0", 8))?,
+        Rule::decimal if pair.as_str().contains(['.', 'e', 'E']) => {
+            out.alloc_float(ctx, pair.as_str().parse().unwrap())?
+        }
+        Rule::decimal => out.number(ctx, parse_integer(pair.as_str(), "", 10))?,
+        Rule::hexadecimal => out.number(ctx, parse_integer(pair.as_str(), "0x", 16))?,
+        Rule::special_float => out.alloc_float(
+            ctx,
+            match pair.as_str() {
+                "+inf.0" => f64::INFINITY,
+                "-inf.0" => f64::NEG_INFINITY,
+                _ => f64::NAN,
+            },
+        )?,
         Rule::symbol => out.intern(ctx, pair.as_str().to_string()),
-        // Rule::string => {
-        //     let inner = pair.into_inner().next().unwrap();
-        //     let ptr = ctx
-        //         .string_arena
-        //         .lock()
-        //         .unwrap()
-        //         .intern(inner.as_str().to_string());
-        //     PackedPtr::str_ptr(ptr)
-        // }
-        Rule::quote | Rule::quasiquote | Rule::unquote => {
+        Rule::string => {
             let inner = pair.into_inner().next().unwrap();
-            let out = sexp_to_object(inner, ctx, out);
+            out.alloc_string(ctx, &unescape(inner.as_str()))?
+        }
+        Rule::quote | Rule::quasiquote | Rule::unquote => {
+            let inner = data(pair.into_inner()).next().unwrap();
+            let out = sexp_to_object(inner, ctx, file, out)?;
             let prefix = match rule {
                 Rule::quote => ctx.common_symbols.quote,
                 Rule::quasiquote => ctx.common_symbols.quasiquote,
                 Rule::unquote => ctx.common_symbols.unquote,
                 _ => unreachable!(),
             };
-            out.singleton(ctx).prepend(ctx, &prefix)
+            out.singleton(ctx)?.prepend(ctx, &prefix)?
         }
         // Rule::pair => {
         //     let mut iter = pair.into_inner();
@@ -105,5 +169,145 @@ fn sexp_to_object<'r>(pair: Pair<Rule>, ctx: &MutatorCtx, out: Slot<'r>) -> Root
             // let ptr = ctx.string_arena.lock().unwrap().intern("nil".to_string());
             out.nil()
         }
+    })
+}
+
+/// Reads an optionally negative literal, becoming a bigint when it does not fit a smallint
+fn parse_integer(str: &str, prefix: &str, radix: u32) -> Number {
+    let (negative, digits) = match str.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, str.strip_prefix('+').unwrap_or(str)),
+    };
+    Number::from_bigint(BigInt::parse(negative, &digits[prefix.len()..], radix))
+}
+
+/// Resolves the escapes accepted by the `char` rule. A `\uXXXX` surrogate pair
+/// is combined into one character; a lone surrogate becomes U+FFFD.
+fn unescape(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    let mut chars = str.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let c = match chars.next().unwrap() {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = hex_escape(&mut chars);
+                if (0xD800..0xDC00).contains(&high) && chars.as_str().starts_with("\\u") {
+                    let mut lookahead = chars.clone();
+                    lookahead.nth(1);
+                    let low = hex_escape(&mut lookahead);
+                    if (0xDC00..0xE000).contains(&low) {
+                        chars = lookahead;
+                        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                        out.push(char::from_u32(code).unwrap());
+                        continue;
+                    }
+                }
+                char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            c => c,
+        };
+        out.push(c);
+    }
+    out
+}
+
+fn hex_escape(chars: &mut Chars) -> u32 {
+    let code = u32::from_str_radix(&chars.as_str()[..4], 16).unwrap();
+    chars.nth(3);
+    code
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{builtins::unpack::unpack_cons, let_slot, source::SourcePos, thread};
+
+    use super::parse_program;
+
+    fn read_program(source: &str) -> Option<String> {
+        let global = Box::leak(Box::new(thread::GlobalState::new()));
+        let ctx = thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: out);
+        let forms = parse_program(source, "test.lisp", &ctx, out).ok()?;
+        Some(unsafe { forms.value().unguard() }.to_string())
+    }
+
+    #[test]
+    fn test_multiple_forms() {
+        assert_eq!(read_program("").unwrap(), "()");
+        assert_eq!(read_program("a (b c)\n'd").unwrap(), "(a (b c) (quote d))");
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(read_program("a ; b (\nc").unwrap(), "(a c)");
+        assert_eq!(read_program("#| a #| (b |# c |# d").unwrap(), "(d)");
+        assert_eq!(
+            read_program("#;(a b) c (d #; e . #;f g) '#;h i").unwrap(),
+            "(c (d . g) (quote i))"
+        );
+        assert_eq!(read_program("(a . #;b)"), None);
+    }
+
+    #[test]
+    fn test_quoted_literals() {
+        assert_eq!(
+            read_program("'\"abc\" '1.5 `\"x\" ,-2 '0x10").unwrap(),
+            "((quote \"abc\") (quote 1.5) (quasiquote \"x\") (unquote -2) (quote 16))"
+        );
+    }
+
+    #[test]
+    fn test_operator_symbols() {
+        assert_eq!(
+            read_program("(<= a b) (- 1) -2 + a-1").unwrap(),
+            "((<= a b) (- 1) -2 + a-1)"
+        );
+        assert_eq!(read_program("(-x) <=a").unwrap(), "((- x) <= a)");
+        assert_eq!(read_program("+1 +0x1f +1.5").unwrap(), "(1 31 1.5)");
+    }
+
+    #[test]
+    fn test_number_delimiters() {
+        assert_eq!(
+            read_program("(1)\"a\"2;b\n3#|c|#-inf.0").unwrap(),
+            "((1) \"a\" 2 3 -inf.0)"
+        );
+        assert_eq!(read_program("(list +1x)"), None);
+        assert_eq!(read_program("1.5.3"), None);
+        assert_eq!(read_program("0x1fg"), None);
+        assert_eq!(read_program("0b12"), None);
+        assert_eq!(read_program("+inf.0a"), None);
+    }
+
+    #[test]
+    fn test_positions_dropped_by_gc() {
+        let global = Box::leak(Box::new(thread::GlobalState::new()));
+        let ctx = thread::MutatorCtx::new_from_global(global);
+
+        let form = {
+            let_slot!(ctx: out);
+            let forms = parse_program("(a b)\n  (c . d)", "test.lisp", &ctx, out).unwrap();
+            let second = unpack_cons(unpack_cons(forms.value()).unwrap().rest).unwrap();
+            let form = unsafe { second.first.unguard() };
+            let pos = SourcePos {
+                file: "test.lisp".into(),
+                line: 2,
+                column: 3,
+            };
+            assert_eq!(ctx.alloc.source_pos(form), Some(pos));
+            form
+        };
+
+        global.alloc_state.lock().unwrap().gc();
+        assert_eq!(ctx.alloc.source_pos(form), None);
     }
 }
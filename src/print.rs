@@ -43,6 +43,89 @@ impl Display for PackedPtr {
             }
             Function(_) => write!(f, "<BUILTIN>"),
             Object(ptr) => write!(f, "<OBJECT {}>", unsafe { *(ptr.as_ptr()) }.first),
+            String(ptr) => write_escaped(f, unsafe { ptr.as_ref() }.as_str()),
+            Float(ptr) => write_float(f, unsafe { ptr.as_ref() }.value),
+            Bigint(ptr) => write!(f, "{}", unsafe { ptr.as_ref() }.to_bigint()),
         }
     }
 }
+
+/// Always prints a decimal point or exponent so the reader sees a float again
+fn write_float(f: &mut std::fmt::Formatter<'_>, n: f64) -> std::fmt::Result {
+    if n.is_nan() {
+        write!(f, "+nan.0")
+    } else if n.is_infinite() {
+        write!(f, "{}inf.0", if n > 0.0 { "+" } else { "-" })
+    } else {
+        write!(f, "{:?}", n)
+    }
+}
+
+/// Writes a string literal that the reader parses back to the same contents
+fn write_escaped(f: &mut std::fmt::Formatter<'_>, str: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in str.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\u{8}' => write!(f, "\\b")?,
+            '\u{c}' => write!(f, "\\f")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{let_slot, parse};
+
+    fn round_trip(source: &str) -> String {
+        let global = Box::leak(Box::new(crate::thread::GlobalState::new()));
+        let ctx = crate::thread::MutatorCtx::new_from_global(global);
+
+        let_slot!(ctx: out);
+        let out = parse::parse(source, &ctx, out).unwrap();
+        unsafe { out.value().unguard() }.to_string()
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(round_trip(r#"" a\"b\\c\n\t""#), r#"" a\"b\\c\n\t""#);
+        assert_eq!(round_trip(r#""é😀\u0001""#), "\"é😀\\u0001\"");
+        assert_eq!(round_trip(r#"("x" . "")"#), r#"("x" . "")"#);
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(
+            round_trip("(1.5 -0.25 2e10 1e300 3.)"),
+            "(1.5 -0.25 20000000000.0 1e300 3.0)"
+        );
+        assert_eq!(
+            round_trip("(+inf.0 -inf.0 +nan.0)"),
+            "(+inf.0 -inf.0 +nan.0)"
+        );
+        assert_eq!(round_trip("(0.1 1)"), "(0.1 1)");
+    }
+
+    #[test]
+    fn test_bigints() {
+        assert_eq!(
+            round_trip("(2305843009213693951 2305843009213693952 -2305843009213693953)"),
+            "(2305843009213693951 2305843009213693952 -2305843009213693953)"
+        );
+        assert_eq!(
+            round_trip("(0x1fffffffffffffffffff -0b101 0777 -0x10)"),
+            "(151115727451828646838271 -5 511 -16)"
+        );
+        assert_eq!(
+            round_trip("(-0b1111111111111111111111111111111111111111111111111111111111111111)"),
+            "(-18446744073709551615)"
+        );
+    }
+}
@@ -1,16 +1,21 @@
 use std::{cell::Cell, fmt::Display, marker::PhantomData, ops::Deref, pin::Pin, ptr::NonNull};
 
 use crate::{
-    alloc::ImmixMutator,
+    alloc::{AllocError, ImmixMutator},
+    bigint::BigInt,
     builtins::BuiltinFunction,
     heap::LAlloc,
     let_slot,
     linked_list::{LinkedList, LinkedListIter, LinkedListNode},
-    object::{PackedPtr, RawCons},
+    number::Number,
+    object::{PackedPtr, RawBigint, RawCons, RawFloat, RawString},
     thread::MutatorCtx,
     value::{Cons, PackedValue, Value},
 };
 
+/// What allocating into a slot gives back; only fails when the heap is exhausted
+pub type AllocResult<'slot> = Result<Root<'slot>, AllocError>;
+
 pub struct Root<'slot> {
     slot: Slot<'slot>,
 }
@@ -39,36 +44,62 @@ impl<'slot> Slot<'slot> {
         root
     }
 
-    pub fn alloc_obj(self, ctx: &MutatorCtx, cons: Cons) -> Root<'slot> {
+    pub fn alloc_obj(self, ctx: &MutatorCtx, cons: Cons) -> AllocResult<'slot> {
+        ctx.alloc.object(
+            |ptr| {
+                self.root_raw(PackedPtr::obj_ptr(unsafe {
+                    NonNull::new_unchecked(ptr.as_ptr() as *mut RawCons)
+                }))
+            },
+            cons,
+        )
+    }
+
+    pub fn alloc_cons(self, ctx: &MutatorCtx, cons: Cons) -> AllocResult<'slot> {
+        ctx.alloc.object(
+            |ptr| {
+                self.root_raw(PackedPtr::cons_ptr(unsafe {
+                    NonNull::new_unchecked(ptr.as_ptr() as *mut RawCons)
+                }))
+            },
+            cons,
+        )
+    }
+
+    pub fn alloc_raw_cons(self, ctx: &MutatorCtx, cons: RawCons) -> AllocResult<'slot> {
         ctx.alloc
-            .object(
-                |ptr| {
-                    self.root_raw(PackedPtr::obj_ptr(unsafe {
-                        NonNull::new_unchecked(ptr.as_ptr() as *mut RawCons)
-                    }))
-                },
-                cons,
-            )
-            .unwrap()
+            .object(|ptr| self.root_raw(PackedPtr::cons_ptr(ptr)), cons)
     }
 
-    pub fn alloc_cons(self, ctx: &MutatorCtx, cons: Cons) -> Root<'slot> {
+    pub fn alloc_string(self, ctx: &MutatorCtx, str: &str) -> AllocResult<'slot> {
         ctx.alloc
-            .object(
-                |ptr| {
-                    self.root_raw(PackedPtr::cons_ptr(unsafe {
-                        NonNull::new_unchecked(ptr.as_ptr() as *mut RawCons)
-                    }))
-                },
-                cons,
-            )
-            .unwrap()
+            .alloc_sized(RawString::alloc_size(str.len()), |ptr| {
+                unsafe { RawString::init(ptr, str) };
+                self.root_raw(PackedPtr::str_ptr(ptr))
+            })
     }
 
-    pub fn alloc_raw_cons(self, ctx: &MutatorCtx, cons: RawCons) -> Root<'slot> {
+    pub fn alloc_float(self, ctx: &MutatorCtx, n: f64) -> AllocResult<'slot> {
+        ctx.alloc.object(
+            |ptr| self.root_raw(PackedPtr::float_ptr(ptr)),
+            RawFloat::new(n),
+        )
+    }
+
+    pub fn alloc_bigint(self, ctx: &MutatorCtx, n: &BigInt) -> AllocResult<'slot> {
         ctx.alloc
-            .object(|ptr| self.root_raw(PackedPtr::cons_ptr(ptr)), cons)
-            .unwrap()
+            .alloc_sized(RawBigint::alloc_size(n.magnitude().len()), |ptr| {
+                unsafe { RawBigint::init(ptr, n) };
+                self.root_raw(PackedPtr::bigint_ptr(ptr))
+            })
+    }
+
+    pub fn number(self, ctx: &MutatorCtx, n: Number) -> AllocResult<'slot> {
+        match n {
+            Number::Integer(n) => Ok(self.root(&Value::Integer(n).pack())),
+            Number::Big(n) => self.alloc_bigint(ctx, &n),
+            Number::Float(n) => self.alloc_float(ctx, n),
+        }
     }
 
     pub fn intern(self, ctx: &MutatorCtx, name: String) -> Root<'slot> {
@@ -88,7 +119,11 @@ impl<'slot> Slot<'slot> {
         self.root_raw(PackedPtr::fun_ptr(fn_ptr))
     }
 
-    pub fn singleton<'guard>(self, ctx: &MutatorCtx, ptr: &PackedValue<'guard>) -> Root<'slot> {
+    pub fn singleton<'guard>(
+        self,
+        ctx: &MutatorCtx,
+        ptr: &PackedValue<'guard>,
+    ) -> AllocResult<'slot> {
         self.alloc_cons(
             ctx,
             Cons {
@@ -112,7 +147,12 @@ impl<'slot> Root<'slot> {
         self.slot
     }
 
-    pub fn map<F: FnOnce(Slot<'slot>, &Root) -> Root<'slot>>(self, f: F) -> Root<'slot> {
+    /// Points the root at `ptr` in place, for roots that are only borrowed
+    pub fn set_raw(&mut self, ptr: PackedPtr) {
+        self.slot.0.ptr.set(ptr);
+    }
+
+    pub fn map<R, F: FnOnce(Slot<'slot>, &Root) -> R>(self, f: F) -> R {
         let tmp = unsafe { RootNode::new() };
         let tmp = unsafe { Slot::new_out_of_list(Pin::new_unchecked(&tmp)) };
         self.slot.0.list.insert_after(tmp.0); // add to list without ctx
@@ -121,7 +161,7 @@ impl<'slot> Root<'slot> {
         f(slot, &tmp)
     }
 
-    pub fn singleton(self, ctx: &MutatorCtx) -> Root<'slot> {
+    pub fn singleton(self, ctx: &MutatorCtx) -> AllocResult<'slot> {
         self.map(|slot, root| {
             slot.alloc_cons(
                 ctx,
@@ -133,7 +173,7 @@ impl<'slot> Root<'slot> {
         })
     }
 
-    pub fn prepend(self, ctx: &MutatorCtx, val: &PackedValue) -> Root<'slot> {
+    pub fn prepend(self, ctx: &MutatorCtx, val: &PackedValue) -> AllocResult<'slot> {
         self.map(|slot, root| {
             slot.alloc_cons(
                 ctx,
@@ -145,7 +185,7 @@ impl<'slot> Root<'slot> {
         })
     }
 
-    pub fn prepend_obj(self, ctx: &MutatorCtx, val: &PackedValue) -> Root<'slot> {
+    pub fn prepend_obj(self, ctx: &MutatorCtx, val: &PackedValue) -> AllocResult<'slot> {
         self.map(|slot, root| {
             slot.alloc_obj(
                 ctx,
@@ -157,24 +197,30 @@ impl<'slot> Root<'slot> {
         })
     }
 
-    pub fn quote(self, ctx: &MutatorCtx) -> Root<'slot> {
-        self.singleton(ctx).prepend(ctx, &ctx.common_symbols.quote)
+    pub fn quote(self, ctx: &MutatorCtx) -> AllocResult<'slot> {
+        self.singleton(ctx)?.prepend(ctx, &ctx.common_symbols.quote)
     }
 
-    pub fn fexpr(self, ctx: &MutatorCtx) -> Root<'slot> {
+    pub fn fexpr(self, ctx: &MutatorCtx) -> AllocResult<'slot> {
         self.prepend_obj(ctx, &ctx.common_symbols.fexpr)
     }
 
-    pub fn _macro(self, ctx: &MutatorCtx) -> Root<'slot> {
+    pub fn _macro(self, ctx: &MutatorCtx) -> AllocResult<'slot> {
         self.prepend_obj(ctx, &ctx.common_symbols._macro)
     }
 }
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq)]
 pub struct Gc<'guard, T> {
     ptr: &'guard T,
 }
 
+impl<'guard, T> Clone for Gc<'guard, T> {
+    fn clone(&self) -> Self {
+        Gc { ptr: self.ptr }
+    }
+}
+
 impl<'guard, T> Gc<'guard, T> {
     pub unsafe fn new(ptr: &'guard T) -> Self {
         Gc { ptr }
@@ -233,6 +279,11 @@ impl RootNode {
     pub fn ptr(&self) -> PackedPtr {
         self.ptr.get()
     }
+
+    /// Lets the collector point the root at an object's new location
+    pub fn set_ptr(&self, ptr: PackedPtr) {
+        self.ptr.set(ptr)
+    }
 }
 
 impl LinkedListNode for RootNode {
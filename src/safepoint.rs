@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, ThreadId},
+};
+
+/// Stops the threads running mutators so that one of them can collect.
+///
+/// Mutators only touch the heap between safepoints: they poll at every
+/// allocation and at the back-edges of the interpreter, and park there while a
+/// collection is pending. The collecting thread waits until every other thread
+/// with a mutator has parked, so that nothing reads or writes objects, roots
+/// or root lists while it marks and sweeps.
+pub struct Safepoint {
+    // set while a collection is pending, so that polling need not lock
+    requested: AtomicBool,
+    state: Mutex<SafepointState>,
+    // signalled whenever a thread parks, leaves or is released
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct SafepointState {
+    // mutators on each thread; a thread parks once however many it has
+    threads: HashMap<ThreadId, usize>,
+    parked: usize,
+    collecting: bool,
+}
+
+impl Safepoint {
+    pub fn new() -> Self {
+        Safepoint {
+            requested: AtomicBool::new(false),
+            state: Mutex::new(SafepointState::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Counts a new mutator on the current thread, once any pending
+    /// collection is over
+    pub fn register(&self) {
+        let mut state = self.wait_for_collection(self.state.lock().unwrap());
+        *state.threads.entry(thread::current().id()).or_insert(0) += 1;
+    }
+
+    /// Forgets a mutator of the current thread. It must not touch the heap again.
+    pub fn unregister(&self) {
+        let mut state = self.state.lock().unwrap();
+        let id = thread::current().id();
+        let count = state.threads.get_mut(&id).expect("mutator not registered");
+        *count -= 1;
+        if *count == 0 {
+            state.threads.remove(&id);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Parks the current thread until a pending collection is over. Objects
+    /// the thread only reaches through unrooted pointers may be freed
+    /// meanwhile; the others stay where they are, since collections only move
+    /// objects while a single thread has mutators.
+    #[inline]
+    pub fn poll(&self) {
+        if self.requested.load(Ordering::Acquire) {
+            self.park();
+        }
+    }
+
+    /// Runs `f`, which must not touch the heap, with the current thread
+    /// counted as parked, so that collections need not wait for `f` to
+    /// return. Returns once no collection is in progress.
+    pub fn park_while<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        let mut state = self.state.lock().unwrap();
+        state.parked += 1;
+        self.changed.notify_all();
+        drop(state);
+
+        let result = f();
+
+        let mut state = self.wait_for_collection(self.state.lock().unwrap());
+        state.parked -= 1;
+        result
+    }
+
+    fn park(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.collecting {
+            state.parked += 1;
+            self.changed.notify_all();
+            state = self.wait_for_collection(state);
+            state.parked -= 1;
+        }
+    }
+
+    fn wait_for_collection<'a>(
+        &self,
+        mut state: MutexGuard<'a, SafepointState>,
+    ) -> MutexGuard<'a, SafepointState> {
+        while state.collecting {
+            state = self.changed.wait(state).unwrap();
+        }
+        state
+    }
+
+    /// Waits until every other thread with a mutator has parked, and keeps
+    /// them parked until the returned guard is dropped. If another thread was
+    /// already stopping the world, parks until its collection is over and
+    /// returns `None` instead, since the heap was just collected.
+    pub fn stop_the_world(&self) -> Option<StoppedWorld<'_>> {
+        let mut state = self.state.lock().unwrap();
+        if state.collecting {
+            drop(state);
+            self.park();
+            return None;
+        }
+
+        state.collecting = true;
+        self.requested.store(true, Ordering::Release);
+        let own = state.threads.contains_key(&thread::current().id()) as usize;
+        while state.parked + own < state.threads.len() {
+            state = self.changed.wait(state).unwrap();
+        }
+        Some(StoppedWorld(self))
+    }
+}
+
+/// Keeps the other mutator threads parked; see `Safepoint::stop_the_world`
+pub struct StoppedWorld<'a>(&'a Safepoint);
+
+impl<'a> StoppedWorld<'a> {
+    /// Whether no thread but the stopping one has mutators, so that nothing
+    /// parked elsewhere holds unrooted pointers into the heap
+    pub fn sole_thread(&self) -> bool {
+        let state = self.0.state.lock().unwrap();
+        let id = thread::current().id();
+        state.threads.keys().all(|&thread| thread == id)
+    }
+}
+
+impl<'a> Drop for StoppedWorld<'a> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.collecting = false;
+        self.0.requested.store(false, Ordering::Release);
+        self.0.changed.notify_all();
+    }
+}
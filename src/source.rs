@@ -0,0 +1,48 @@
+use std::{collections::HashMap, fmt::Display, mem::take, sync::Arc};
+
+use crate::object::PackedPtr;
+
+/// Where the reader found a form. Lines and columns count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourcePos {
+    pub file: Arc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for SourcePos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Positions of the lists built by the reader, keyed by the address of their
+/// first cons. The collector drops the entries of conses it frees, since their
+/// addresses are reused by later allocations.
+#[derive(Default)]
+pub struct SourceMap {
+    positions: HashMap<PackedPtr, SourcePos>,
+}
+
+// the pointers are only keys, which threads look up under its lock and
+// collections relocate while the mutators are stopped
+unsafe impl Send for SourceMap {}
+
+impl SourceMap {
+    pub fn insert(&mut self, ptr: PackedPtr, pos: SourcePos) {
+        self.positions.insert(ptr, pos);
+    }
+
+    pub fn get(&self, ptr: PackedPtr) -> Option<&SourcePos> {
+        self.positions.get(&ptr)
+    }
+
+    /// Moves each entry to the address `relocate` gives its cons after a
+    /// collection, or drops it if `relocate` returns `None` for a freed cons
+    pub fn relocate<F: FnMut(PackedPtr) -> Option<PackedPtr>>(&mut self, mut relocate: F) {
+        self.positions = take(&mut self.positions)
+            .into_iter()
+            .filter_map(|(ptr, pos)| Some((relocate(ptr)?, pos)))
+            .collect();
+    }
+}
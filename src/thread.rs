@@ -1,40 +1,172 @@
-use std::sync::Mutex;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+};
 
 use crate::{
-    alloc::{GlobalImmixAllocator, ImmixMutator},
+    alloc::{AllocError, GcStats, GlobalImmixAllocator, ImmixMutator},
     arena::{Arena, CommonSymbols},
+    builtins::BuiltinError,
+    object::PackedPtr,
 };
 
+/// Native stack one nested evaluation may take, with room to spare in debug
+/// builds
+const EVAL_FRAME_SIZE: usize = 8 * 1024;
+
+/// Nested evaluations a thread with `stack_size` bytes of stack can run before
+/// `rust_eval` fails with `StackOverflow` rather than overflowing the stack
+pub const fn max_eval_depth_for(stack_size: usize) -> usize {
+    stack_size / EVAL_FRAME_SIZE
+}
+
+/// Nested evaluations allowed by default, safe on the 2 MiB stack threads get
+/// unless they ask for more. Threads started with a larger stack, such as
+/// `EVAL_STACK_SIZE`, raise `max_eval_depth` to match.
+pub const DEFAULT_MAX_EVAL_DEPTH: usize = max_eval_depth_for(2 * 1024 * 1024);
+
+/// Native stack size the interpreter and its spawned threads run on, for deep
+/// recursion
+pub const EVAL_STACK_SIZE: usize = 256 * 1024 * 1024;
+
 pub struct GlobalState {
     pub alloc_state: Mutex<GlobalImmixAllocator>,
-    pub string_arena: Mutex<Arena>,
+    pub string_arena: Arc<Mutex<Arena>>,
     pub common_symbols: &'static CommonSymbols,
+    pub threads: Mutex<ThreadTable>,
+    pub monitor: Monitor,
 }
 
 impl GlobalState {
     pub fn new() -> Self {
         let mut arena = Arena::new();
         let common_symbols = Box::leak(Box::new(CommonSymbols::new(&mut arena)));
+        let arena = Arc::new(Mutex::new(arena));
         GlobalState {
-            alloc_state: Mutex::new(GlobalImmixAllocator::new()),
-            string_arena: Mutex::new(arena),
+            alloc_state: Mutex::new(GlobalImmixAllocator::with_symbols(arena.clone())),
+            string_arena: arena,
             common_symbols: common_symbols,
+            threads: Mutex::new(ThreadTable::default()),
+            monitor: Monitor::default(),
         }
     }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.alloc_state.lock().unwrap().stats()
+    }
 }
 
 pub struct MutatorCtx {
+    pub global: &'static GlobalState,
     pub alloc: ImmixMutator<'static>,
     pub string_arena: &'static Mutex<Arena>,
     pub common_symbols: &'static CommonSymbols,
+    pub eval_depth: Cell<usize>,
+    pub max_eval_depth: Cell<usize>,
 }
 
 impl MutatorCtx {
+    /// Panics if the heap has no room for the mutator's first block; see
+    /// `try_new_from_global`
     pub fn new_from_global(global: &'static GlobalState) -> Self {
-        MutatorCtx {
-            alloc: ImmixMutator::new(&global.alloc_state),
+        Self::try_new_from_global(global).expect("no room in the heap for a new mutator")
+    }
+
+    pub fn try_new_from_global(global: &'static GlobalState) -> Result<Self, AllocError> {
+        Ok(MutatorCtx {
+            global,
+            alloc: ImmixMutator::new(&global.alloc_state)?,
             string_arena: &global.string_arena,
             common_symbols: &global.common_symbols,
+            eval_depth: Cell::new(0),
+            max_eval_depth: Cell::new(DEFAULT_MAX_EVAL_DEPTH),
+        })
+    }
+
+    /// Counts one more level of evaluation until the returned guard is dropped
+    pub fn enter_eval(&self) -> Result<EvalDepthGuard<'_>, BuiltinError> {
+        let depth = self.eval_depth.get();
+        if depth >= self.max_eval_depth.get() {
+            return Err(BuiltinError::StackOverflow(depth));
         }
+        self.eval_depth.set(depth + 1);
+        Ok(EvalDepthGuard(&self.eval_depth))
+    }
+}
+
+pub struct EvalDepthGuard<'a>(&'a Cell<usize>);
+
+impl<'a> Drop for EvalDepthGuard<'a> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1);
+    }
+}
+
+/// A pointer into the heap handed between threads. The sender must keep it
+/// rooted until the receiver has rooted it.
+pub struct SendPtr(pub PackedPtr);
+
+unsafe impl Send for SendPtr {}
+
+/// What a spawned thread hands its joiner: its result, or its error as text
+pub type ThreadResult = Result<SendPtr, String>;
+
+/// A thread started by `spawn`. It keeps its result rooted until `done` is
+/// sent, after the joining thread has rooted it too.
+pub struct SpawnedThread {
+    pub handle: JoinHandle<()>,
+    pub result: Receiver<ThreadResult>,
+    pub done: Sender<()>,
+}
+
+/// Threads started by `spawn` that have not been joined, by id
+#[derive(Default)]
+pub struct ThreadTable {
+    pub next_id: usize,
+    pub threads: HashMap<usize, SpawnedThread>,
+}
+
+/// Guards the state of every channel and mutex object, which lives in the
+/// heap. Holders must not allocate, or they could park at a safepoint while
+/// another thread waits on the lock without parking.
+#[derive(Default)]
+pub struct Monitor {
+    // bumped on every change, so that waiters can tell they missed none
+    generation: Mutex<usize>,
+    changed: Condvar,
+}
+
+impl Monitor {
+    pub fn lock(&self) -> MutexGuard<'_, usize> {
+        self.generation.lock().unwrap()
+    }
+
+    /// Wakes the threads waiting for a change
+    pub fn notify(&self, guard: &mut MutexGuard<'_, usize>) {
+        **guard += 1;
+        self.changed.notify_all();
+    }
+
+    /// Releases the lock until another thread notifies, parked meanwhile so
+    /// that collections go ahead, and takes it again
+    pub fn wait<'a>(
+        &'a self,
+        ctx: &MutatorCtx,
+        guard: MutexGuard<'a, usize>,
+    ) -> MutexGuard<'a, usize> {
+        let generation = *guard;
+        ctx.alloc.park_while(|| {
+            drop(
+                self.changed
+                    .wait_while(guard, |g| *g == generation)
+                    .unwrap(),
+            )
+        });
+        self.lock()
     }
 }
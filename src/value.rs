@@ -2,7 +2,7 @@ use std::{marker::PhantomData, ops::Deref, ptr::NonNull};
 
 use crate::{
     builtins::BuiltinFunction,
-    object::{self, LString, PackedPtr, UnpackedPtr},
+    object::{self, LString, PackedPtr, RawBigint, RawFloat, RawString, UnpackedPtr},
     root::Gc,
     util::construct_non_null,
 };
@@ -42,6 +42,9 @@ pub enum Value<'guard> {
     Object(Gc<'guard, Cons<'guard>>),
     Symbol(Gc<'guard, LString>),
     Function(BuiltinFunction),
+    String(Gc<'guard, RawString>),
+    Float(Gc<'guard, RawFloat>),
+    Bigint(Gc<'guard, RawBigint>),
     Nil,
 }
 
@@ -58,6 +61,9 @@ impl<'guard> Value<'guard> {
             UnpackedPtr::Nil => Self::Nil,
             UnpackedPtr::Symbol(ptr) => Self::Symbol(Gc::new(ptr.as_ref())),
             UnpackedPtr::Function(ptr) => Self::Function(ptr),
+            UnpackedPtr::String(ptr) => Self::String(Gc::new(ptr.as_ref())),
+            UnpackedPtr::Float(ptr) => Self::Float(Gc::new(ptr.as_ref())),
+            UnpackedPtr::Bigint(ptr) => Self::Bigint(Gc::new(ptr.as_ref())),
         }
     }
 
@@ -72,6 +78,9 @@ impl<'guard> Value<'guard> {
             )),
             Value::Symbol(ptr) => UnpackedPtr::Symbol(ptr.as_raw()),
             Value::Function(ptr) => UnpackedPtr::Function(*ptr),
+            Value::String(ptr) => UnpackedPtr::String(ptr.as_raw()),
+            Value::Float(ptr) => UnpackedPtr::Float(ptr.as_raw()),
+            Value::Bigint(ptr) => UnpackedPtr::Bigint(ptr.as_raw()),
             Value::Nil => UnpackedPtr::Nil,
         }
     }